@@ -1,17 +1,59 @@
-use crate::{chunk::RawChunk, ihdr::IhdrChunk};
+use crate::{
+    chunk::RawChunk,
+    color_type::ColorType,
+    error::{PngError, Result},
+    ihdr::IhdrChunk,
+    limits::DecodeLimits,
+};
 
-use anyhow::Result;
 use color_print::cprintln;
 use text::{CompressedTextChunk, InternationalTextChunk, TextChunk};
 
-use self::{background::Background, gama::Gama, phys::PhysicalUnits, time::Time};
+use self::{
+    background::Background,
+    chrm::Chromaticities,
+    cicp::CodingIndependentCodePoints,
+    clli::ContentLightLevel,
+    exif::Exif,
+    gama::Gama,
+    gif::{GifApplicationExtension, GifGraphicControl},
+    hist::Histogram,
+    iccp::IccProfileChunk,
+    mdcv::MasteringDisplayColorVolume,
+    offs::ImageOffset,
+    pcal::PixelCalibration,
+    phys::PhysicalUnits,
+    sbit::SignificantBits,
+    scal::PhysicalScale,
+    splt::SuggestedPalette,
+    srgb::Srgb,
+    ster::Stereo,
+    time::Time,
+};
 
 pub mod background;
+pub mod chrm;
+pub mod cicp;
+pub mod clli;
+pub mod exif;
 pub mod gama;
+pub mod gif;
+pub mod hist;
+pub mod iccp;
+pub mod mdcv;
+pub mod offs;
+pub mod pcal;
 pub mod phys;
+pub mod sbit;
+pub mod scal;
+pub mod splt;
+pub mod srgb;
+pub mod ster;
 pub mod text;
 pub mod time;
 
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct AncillaryChunks<'a>(pub Vec<AncillaryChunk<'a>>);
 
 impl AncillaryChunks<'_> {
@@ -34,6 +76,168 @@ impl AncillaryChunks<'_> {
             })
             .next()
     }
+
+    pub fn get_srgb(&self) -> Option<Srgb> {
+        self.0
+            .iter()
+            .filter_map(|s| match s {
+                AncillaryChunk::sRGB(c) => Some(*c),
+                _ => None,
+            })
+            .next()
+    }
+
+    pub fn get_chromaticities(&self) -> Option<Chromaticities> {
+        self.0
+            .iter()
+            .filter_map(|s| match s {
+                AncillaryChunk::cHRM(c) => Some(*c),
+                _ => None,
+            })
+            .next()
+    }
+
+    pub fn get_sbit(&self) -> Option<SignificantBits> {
+        self.0
+            .iter()
+            .filter_map(|s| match s {
+                AncillaryChunk::sBIT(c) => Some(*c),
+                _ => None,
+            })
+            .next()
+    }
+
+    pub fn get_histogram(&self) -> Option<&Histogram> {
+        self.0
+            .iter()
+            .filter_map(|s| match s {
+                AncillaryChunk::hIST(c) => Some(c),
+                _ => None,
+            })
+            .next()
+    }
+
+    /// Every suggested palette, there can be several with different names
+    pub fn get_suggested_palettes(&self) -> Vec<&SuggestedPalette<'_>> {
+        self.0
+            .iter()
+            .filter_map(|s| match s {
+                AncillaryChunk::sPLT(c) => Some(c),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn get_exif(&self) -> Option<&Exif<'_>> {
+        self.0
+            .iter()
+            .filter_map(|s| match s {
+                AncillaryChunk::eXIf(c) => Some(c),
+                _ => None,
+            })
+            .next()
+    }
+
+    pub fn get_cicp(&self) -> Option<CodingIndependentCodePoints> {
+        self.0
+            .iter()
+            .filter_map(|s| match s {
+                AncillaryChunk::cICP(c) => Some(*c),
+                _ => None,
+            })
+            .next()
+    }
+
+    pub fn get_mastering_display(&self) -> Option<MasteringDisplayColorVolume> {
+        self.0
+            .iter()
+            .filter_map(|s| match s {
+                AncillaryChunk::mDCV(c) => Some(*c),
+                _ => None,
+            })
+            .next()
+    }
+
+    pub fn get_content_light_level(&self) -> Option<ContentLightLevel> {
+        self.0
+            .iter()
+            .filter_map(|s| match s {
+                AncillaryChunk::cLLI(c) => Some(*c),
+                _ => None,
+            })
+            .next()
+    }
+
+    pub fn get_offset(&self) -> Option<ImageOffset> {
+        self.0
+            .iter()
+            .filter_map(|s| match s {
+                AncillaryChunk::oFFs(c) => Some(*c),
+                _ => None,
+            })
+            .next()
+    }
+
+    /// Maps raw samples to physical values with [`PixelCalibration::physical_value`]
+    pub fn get_calibration(&self) -> Option<&PixelCalibration<'_>> {
+        self.0
+            .iter()
+            .filter_map(|s| match s {
+                AncillaryChunk::pCAL(c) => Some(c),
+                _ => None,
+            })
+            .next()
+    }
+
+    pub fn get_scale(&self) -> Option<&PhysicalScale> {
+        self.0
+            .iter()
+            .filter_map(|s| match s {
+                AncillaryChunk::sCAL(c) => Some(c),
+                _ => None,
+            })
+            .next()
+    }
+
+    pub fn get_physical_units(&self) -> Option<PhysicalUnits> {
+        self.0
+            .iter()
+            .filter_map(|s| match s {
+                AncillaryChunk::pHYs(c) => Some(*c),
+                _ => None,
+            })
+            .next()
+    }
+
+    pub fn get_stereo(&self) -> Option<Stereo> {
+        self.0
+            .iter()
+            .filter_map(|s| match s {
+                AncillaryChunk::sTER(c) => Some(*c),
+                _ => None,
+            })
+            .next()
+    }
+
+    pub fn get_gif_graphic_control(&self) -> Option<GifGraphicControl> {
+        self.0
+            .iter()
+            .filter_map(|s| match s {
+                AncillaryChunk::gIFg(c) => Some(*c),
+                _ => None,
+            })
+            .next()
+    }
+
+    pub fn get_iccp(&self) -> Option<&IccProfileChunk<'_>> {
+        self.0
+            .iter()
+            .filter_map(|s| match s {
+                AncillaryChunk::iCCP(c) => Some(c),
+                _ => None,
+            })
+            .next()
+    }
 }
 
 #[derive(Debug)]
@@ -41,11 +245,27 @@ impl AncillaryChunks<'_> {
 pub enum AncillaryChunk<'a> {
     tIME(Time),
     gAMA(Gama),
+    cHRM(Chromaticities),
+    sRGB(Srgb),
+    iCCP(IccProfileChunk<'a>),
+    sBIT(SignificantBits),
+    hIST(Histogram),
+    sPLT(SuggestedPalette<'a>),
+    eXIf(Exif<'a>),
+    cICP(CodingIndependentCodePoints),
+    mDCV(MasteringDisplayColorVolume),
+    cLLI(ContentLightLevel),
+    sTER(Stereo),
+    gIFg(GifGraphicControl),
+    gIFx(GifApplicationExtension<'a>),
     bKGD(Background),
     tEXt(TextChunk<'a>),
     zTXt(CompressedTextChunk<'a>),
     iTXt(InternationalTextChunk<'a>),
     pHYs(PhysicalUnits),
+    oFFs(ImageOffset),
+    pCAL(PixelCalibration<'a>),
+    sCAL(PhysicalScale),
     Unknown(RawChunk<'a>),
 }
 
@@ -53,50 +273,226 @@ impl<'a> AncillaryChunk<'a> {
     pub fn chunk_type(&self) -> &'a str {
         match &self {
             AncillaryChunk::pHYs(_) => PhysicalUnits::CHUNK_TYPE,
+            AncillaryChunk::oFFs(_) => ImageOffset::CHUNK_TYPE,
+            AncillaryChunk::pCAL(_) => PixelCalibration::CHUNK_TYPE,
+            AncillaryChunk::sCAL(_) => PhysicalScale::CHUNK_TYPE,
             AncillaryChunk::bKGD(_) => Background::CHUNK_TYPE,
             AncillaryChunk::tEXt(_) => TextChunk::CHUNK_TYPE,
             AncillaryChunk::zTXt(_) => CompressedTextChunk::CHUNK_TYPE,
             AncillaryChunk::iTXt(_) => InternationalTextChunk::CHUNK_TYPE,
             AncillaryChunk::tIME(_) => Time::CHUNK_TYPE,
             AncillaryChunk::gAMA(_) => Gama::CHUNK_TYPE,
+            AncillaryChunk::cHRM(_) => Chromaticities::CHUNK_TYPE,
+            AncillaryChunk::sRGB(_) => Srgb::CHUNK_TYPE,
+            AncillaryChunk::iCCP(_) => IccProfileChunk::CHUNK_TYPE,
+            AncillaryChunk::sBIT(_) => SignificantBits::CHUNK_TYPE,
+            AncillaryChunk::hIST(_) => Histogram::CHUNK_TYPE,
+            AncillaryChunk::sPLT(_) => SuggestedPalette::CHUNK_TYPE,
+            AncillaryChunk::eXIf(_) => Exif::CHUNK_TYPE,
+            AncillaryChunk::cICP(_) => CodingIndependentCodePoints::CHUNK_TYPE,
+            AncillaryChunk::mDCV(_) => MasteringDisplayColorVolume::CHUNK_TYPE,
+            AncillaryChunk::cLLI(_) => ContentLightLevel::CHUNK_TYPE,
+            AncillaryChunk::sTER(_) => Stereo::CHUNK_TYPE,
+            AncillaryChunk::gIFg(_) => GifGraphicControl::CHUNK_TYPE,
+            AncillaryChunk::gIFx(_) => GifApplicationExtension::CHUNK_TYPE,
             AncillaryChunk::Unknown(c) => c.chunk_type,
         }
     }
+
+    /// Whether the chunk can describe an image with `ihdr`, which may not be the one it
+    /// was read with: hIST needs a palette of the same size, bKGD a color the palette holds
+    pub fn fits(&self, ihdr: &IhdrChunk) -> bool {
+        match self {
+            AncillaryChunk::hIST(chunk) => matches!(
+                &ihdr.color_type,
+                ColorType::Palette(palette) if palette.entries.len() == chunk.frequencies.len()
+            ),
+            AncillaryChunk::bKGD(chunk) => chunk.to_bytes(&ihdr.color_type, ihdr.bit_depth).is_ok(),
+            _ => true,
+        }
+    }
+
+    /// Serializes the chunk content for an image described by `ihdr`,
+    /// sBIT is converted to its color type and sample depth
+    pub fn to_bytes(&self, ihdr: &IhdrChunk) -> Result<Vec<u8>> {
+        Ok(match self {
+            AncillaryChunk::tIME(chunk) => chunk.to_bytes(),
+            AncillaryChunk::gAMA(chunk) => chunk.to_bytes(),
+            AncillaryChunk::cHRM(chunk) => chunk.to_bytes(),
+            AncillaryChunk::sRGB(chunk) => chunk.to_bytes(),
+            AncillaryChunk::iCCP(chunk) => chunk.to_bytes(),
+            AncillaryChunk::sBIT(chunk) => chunk
+                .convert(&ihdr.color_type, ihdr.sample_depth())
+                .to_bytes(),
+            AncillaryChunk::hIST(chunk) => chunk.to_bytes(),
+            AncillaryChunk::sPLT(chunk) => chunk.to_bytes(),
+            AncillaryChunk::eXIf(chunk) => chunk.to_bytes(),
+            AncillaryChunk::cICP(chunk) => chunk.to_bytes(),
+            AncillaryChunk::mDCV(chunk) => chunk.to_bytes(),
+            AncillaryChunk::cLLI(chunk) => chunk.to_bytes(),
+            AncillaryChunk::sTER(chunk) => chunk.to_bytes(),
+            AncillaryChunk::gIFg(chunk) => chunk.to_bytes(),
+            AncillaryChunk::gIFx(chunk) => chunk.to_bytes(),
+            AncillaryChunk::bKGD(chunk) => chunk.to_bytes(&ihdr.color_type, ihdr.bit_depth)?,
+            AncillaryChunk::tEXt(chunk) => chunk.to_bytes(),
+            AncillaryChunk::zTXt(chunk) => chunk.to_bytes(),
+            AncillaryChunk::iTXt(chunk) => chunk.to_bytes(),
+            AncillaryChunk::pHYs(chunk) => chunk.to_bytes(),
+            AncillaryChunk::oFFs(chunk) => chunk.to_bytes(),
+            AncillaryChunk::pCAL(chunk) => chunk.to_bytes(),
+            AncillaryChunk::sCAL(chunk) => chunk.to_bytes(),
+            AncillaryChunk::Unknown(chunk) => chunk.data.to_vec(),
+        })
+    }
+}
+
+/// Serialized as the chunk type and the parsed content of the chunk
+#[cfg(feature = "serde")]
+impl serde::Serialize for AncillaryChunk<'_> {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("AncillaryChunk", 2)?;
+        state.serialize_field("chunk_type", self.chunk_type())?;
+        match self {
+            AncillaryChunk::tIME(chunk) => state.serialize_field("content", chunk)?,
+            AncillaryChunk::gAMA(chunk) => state.serialize_field("content", chunk)?,
+            AncillaryChunk::cHRM(chunk) => state.serialize_field("content", chunk)?,
+            AncillaryChunk::sRGB(chunk) => state.serialize_field("content", chunk)?,
+            AncillaryChunk::iCCP(chunk) => state.serialize_field("content", chunk)?,
+            AncillaryChunk::sBIT(chunk) => state.serialize_field("content", chunk)?,
+            AncillaryChunk::hIST(chunk) => state.serialize_field("content", chunk)?,
+            AncillaryChunk::sPLT(chunk) => state.serialize_field("content", chunk)?,
+            AncillaryChunk::eXIf(chunk) => state.serialize_field("content", chunk)?,
+            AncillaryChunk::cICP(chunk) => state.serialize_field("content", chunk)?,
+            AncillaryChunk::mDCV(chunk) => state.serialize_field("content", chunk)?,
+            AncillaryChunk::cLLI(chunk) => state.serialize_field("content", chunk)?,
+            AncillaryChunk::sTER(chunk) => state.serialize_field("content", chunk)?,
+            AncillaryChunk::gIFg(chunk) => state.serialize_field("content", chunk)?,
+            AncillaryChunk::gIFx(chunk) => state.serialize_field("content", chunk)?,
+            AncillaryChunk::bKGD(chunk) => state.serialize_field("content", chunk)?,
+            AncillaryChunk::tEXt(chunk) => state.serialize_field("content", chunk)?,
+            AncillaryChunk::zTXt(chunk) => state.serialize_field("content", chunk)?,
+            AncillaryChunk::iTXt(chunk) => state.serialize_field("content", chunk)?,
+            AncillaryChunk::pHYs(chunk) => state.serialize_field("content", chunk)?,
+            AncillaryChunk::oFFs(chunk) => state.serialize_field("content", chunk)?,
+            AncillaryChunk::pCAL(chunk) => state.serialize_field("content", chunk)?,
+            AncillaryChunk::sCAL(chunk) => state.serialize_field("content", chunk)?,
+            AncillaryChunk::Unknown(chunk) => state.serialize_field("content", chunk)?,
+        }
+        state.end()
+    }
+}
+
+fn parse_ancillary_chunk<'a>(
+    chunk: RawChunk<'a>,
+    ihdr: &IhdrChunk,
+    palette_len: Option<usize>,
+    limits: &DecodeLimits,
+) -> Result<AncillaryChunk<'a>> {
+    Ok(match chunk.chunk_type {
+        TextChunk::CHUNK_TYPE => AncillaryChunk::tEXt(TextChunk::parse(chunk.data, limits)?),
+        CompressedTextChunk::CHUNK_TYPE => {
+            AncillaryChunk::zTXt(CompressedTextChunk::parse(chunk.data, limits)?)
+        }
+        InternationalTextChunk::CHUNK_TYPE => {
+            AncillaryChunk::iTXt(InternationalTextChunk::parse(chunk.data, limits)?)
+        }
+        Background::CHUNK_TYPE => AncillaryChunk::bKGD(Background::parse(
+            chunk.data,
+            &ihdr.color_type,
+            ihdr.bit_depth,
+        )?),
+        Time::CHUNK_TYPE => AncillaryChunk::tIME(Time::parse(chunk.data)?),
+        PhysicalUnits::CHUNK_TYPE => {
+            AncillaryChunk::pHYs(PhysicalUnits::parse(chunk.data, ihdr.width, ihdr.height)?)
+        }
+        ImageOffset::CHUNK_TYPE => AncillaryChunk::oFFs(ImageOffset::parse(chunk.data)?),
+        PixelCalibration::CHUNK_TYPE => AncillaryChunk::pCAL(PixelCalibration::parse(chunk.data)?),
+        PhysicalScale::CHUNK_TYPE => AncillaryChunk::sCAL(PhysicalScale::parse(chunk.data)?),
+        Gama::CHUNK_TYPE => AncillaryChunk::gAMA(Gama::parse(chunk.data)?),
+        Chromaticities::CHUNK_TYPE => AncillaryChunk::cHRM(Chromaticities::parse(chunk.data)?),
+        Srgb::CHUNK_TYPE => AncillaryChunk::sRGB(Srgb::parse(chunk.data)?),
+        IccProfileChunk::CHUNK_TYPE => {
+            AncillaryChunk::iCCP(IccProfileChunk::parse(chunk.data, limits)?)
+        }
+        SignificantBits::CHUNK_TYPE => AncillaryChunk::sBIT(SignificantBits::parse(
+            chunk.data,
+            &ihdr.color_type,
+            ihdr.sample_depth(),
+        )?),
+        Histogram::CHUNK_TYPE => AncillaryChunk::hIST(Histogram::parse(chunk.data, palette_len)?),
+        SuggestedPalette::CHUNK_TYPE => AncillaryChunk::sPLT(SuggestedPalette::parse(chunk.data)?),
+        Exif::CHUNK_TYPE => AncillaryChunk::eXIf(Exif::parse(chunk.data)?),
+        CodingIndependentCodePoints::CHUNK_TYPE => {
+            AncillaryChunk::cICP(CodingIndependentCodePoints::parse(chunk.data)?)
+        }
+        MasteringDisplayColorVolume::CHUNK_TYPE
+        | MasteringDisplayColorVolume::LEGACY_CHUNK_TYPE => {
+            AncillaryChunk::mDCV(MasteringDisplayColorVolume::parse(chunk.data)?)
+        }
+        ContentLightLevel::CHUNK_TYPE | ContentLightLevel::LEGACY_CHUNK_TYPE => {
+            AncillaryChunk::cLLI(ContentLightLevel::parse(chunk.data)?)
+        }
+        Stereo::CHUNK_TYPE => AncillaryChunk::sTER(Stereo::parse(chunk.data, ihdr.width)?),
+        GifGraphicControl::CHUNK_TYPE => {
+            AncillaryChunk::gIFg(GifGraphicControl::parse(chunk.data)?)
+        }
+        GifApplicationExtension::CHUNK_TYPE => {
+            AncillaryChunk::gIFx(GifApplicationExtension::parse(chunk.data)?)
+        }
+
+        _ if !chunk.is_ancillary() => {
+            return Err(PngError::UnknownCritical {
+                chunk_type: chunk.chunk_type.to_string(),
+                offset: chunk.offset,
+            })
+        }
+        _ => AncillaryChunk::Unknown(chunk),
+    })
 }
 
+/// Unrecognized ancillary chunks are kept as `Unknown`, unrecognized critical chunks are an error
+/// `palette_len` is the number of entries of the PLTE chunk, truecolor images can have one as a
+/// suggested palette
 pub fn parse_ancillary_chunks<'a>(
     chunks: Vec<RawChunk<'a>>,
     ihdr: &IhdrChunk,
-) -> anyhow::Result<Vec<AncillaryChunk<'a>>> {
+    palette_len: Option<usize>,
+    limits: &DecodeLimits,
+) -> Result<Vec<AncillaryChunk<'a>>> {
     chunks
         .into_iter()
-        .map(|chunk| -> anyhow::Result<_> {
-            match chunk.chunk_type {
-                TextChunk::CHUNK_TYPE => Ok(AncillaryChunk::tEXt(TextChunk::parse(chunk.data)?)),
-                CompressedTextChunk::CHUNK_TYPE => Ok(AncillaryChunk::zTXt(
-                    CompressedTextChunk::parse(chunk.data)?,
-                )),
-                InternationalTextChunk::CHUNK_TYPE => Ok(AncillaryChunk::iTXt(
-                    InternationalTextChunk::parse(chunk.data)?,
-                )),
-                Background::CHUNK_TYPE => Ok(AncillaryChunk::bKGD(Background::parse(
-                    chunk.data,
-                    &ihdr.color_type,
-                    ihdr.bit_depth,
-                )?)),
-                Time::CHUNK_TYPE => Ok(AncillaryChunk::tIME(Time::parse(chunk.data)?)),
-                PhysicalUnits::CHUNK_TYPE => Ok(AncillaryChunk::pHYs(PhysicalUnits::parse(
-                    chunk.data,
-                    ihdr.width,
-                    ihdr.height,
-                )?)),
-                Gama::CHUNK_TYPE => Ok(AncillaryChunk::gAMA(Gama::parse(chunk.data)?)),
-
-                _ => Ok(AncillaryChunk::Unknown(chunk)),
-            }
+        .map(|chunk| {
+            let offset = chunk.offset;
+            parse_ancillary_chunk(chunk, ihdr, palette_len, limits).map_err(|e| e.at_offset(offset))
         })
-        .collect::<Result<Vec<_>, _>>()
+        .collect()
 }
+
+/// Parses the chunks like [`parse_ancillary_chunks`], chunks that can't be parsed are left out
+/// and returned as warnings
+pub fn parse_ancillary_chunks_lenient<'a>(
+    chunks: Vec<RawChunk<'a>>,
+    ihdr: &IhdrChunk,
+    palette_len: Option<usize>,
+    limits: &DecodeLimits,
+) -> (Vec<AncillaryChunk<'a>>, Vec<PngError>) {
+    let mut parsed = Vec::new();
+    let mut warnings = Vec::new();
+    for chunk in chunks {
+        let offset = chunk.offset;
+        match parse_ancillary_chunk(chunk, ihdr, palette_len, limits) {
+            Ok(chunk) => parsed.push(chunk),
+            Err(err) => warnings.push(err.at_offset(offset)),
+        }
+    }
+    (parsed, warnings)
+}
+
 impl<'a> AncillaryChunk<'a> {
     pub fn print(&self) {
         cprintln!("<cyan>Chunk Type: {}</cyan>", self.chunk_type());
@@ -104,6 +500,45 @@ impl<'a> AncillaryChunk<'a> {
             AncillaryChunk::gAMA(chunk) => {
                 cprintln!("<green>{}</green>", chunk);
             }
+            AncillaryChunk::cHRM(chunk) => {
+                cprintln!("<green>{}</green>", chunk);
+            }
+            AncillaryChunk::sRGB(chunk) => {
+                cprintln!("<green>{}</green>", chunk);
+            }
+            AncillaryChunk::iCCP(chunk) => {
+                cprintln!("<green>{}</green>", chunk);
+            }
+            AncillaryChunk::sBIT(chunk) => {
+                cprintln!("<green>{}</green>", chunk);
+            }
+            AncillaryChunk::hIST(chunk) => {
+                cprintln!("<green>{}</green>", chunk);
+            }
+            AncillaryChunk::sPLT(chunk) => {
+                cprintln!("<green>{}</green>", chunk);
+            }
+            AncillaryChunk::eXIf(chunk) => {
+                cprintln!("<green>{}</green>", chunk);
+            }
+            AncillaryChunk::cICP(chunk) => {
+                cprintln!("<green>{}</green>", chunk);
+            }
+            AncillaryChunk::mDCV(chunk) => {
+                cprintln!("<green>{}</green>", chunk);
+            }
+            AncillaryChunk::cLLI(chunk) => {
+                cprintln!("<green>{}</green>", chunk);
+            }
+            AncillaryChunk::sTER(chunk) => {
+                cprintln!("<green>{}</green>", chunk);
+            }
+            AncillaryChunk::gIFg(chunk) => {
+                cprintln!("<green>{}</green>", chunk);
+            }
+            AncillaryChunk::gIFx(chunk) => {
+                cprintln!("<green>{}</green>", chunk);
+            }
 
             AncillaryChunk::tEXt(chunk) => {
                 cprintln!("<green>{}</green>", chunk);
@@ -123,9 +558,100 @@ impl<'a> AncillaryChunk<'a> {
             AncillaryChunk::pHYs(chunk) => {
                 cprintln!("<green>{}</green>", chunk);
             }
-            AncillaryChunk::Unknown(_) => {
-                cprintln!("<green>Unknown chunk type</green>")
+            AncillaryChunk::oFFs(chunk) => {
+                cprintln!("<green>{}</green>", chunk);
+            }
+            AncillaryChunk::pCAL(chunk) => {
+                cprintln!("<green>{}</green>", chunk);
+            }
+            AncillaryChunk::sCAL(chunk) => {
+                cprintln!("<green>{}</green>", chunk);
+            }
+            AncillaryChunk::Unknown(chunk) => {
+                cprintln!("<green>Unknown chunk type</green>");
+                cprintln!(
+                    "<green>Private: {}, safe to copy: {}</green>",
+                    chunk.is_private(),
+                    chunk.is_safe_to_copy()
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ihdr::{CompressionMethod, FilterMethod, InterlaceMethod};
+
+    #[test]
+    fn legacy_hdr_chunk_names_are_accepted() {
+        let ihdr = IhdrChunk {
+            width: 1,
+            height: 1,
+            bit_depth: 16,
+            color_type: ColorType::Rgb { transparent: None },
+            compression_method: CompressionMethod::Zlib,
+            filter_method: FilterMethod::FiveFilter,
+            interlace_method: InterlaceMethod::None,
+        };
+        let clli = [0, 0x0f, 0x42, 0x40, 0, 0x03, 0x0d, 0x40];
+        let mdcv = [0u8; 24];
+        for (chunk_type, data) in [
+            (ContentLightLevel::CHUNK_TYPE, &clli[..]),
+            (ContentLightLevel::LEGACY_CHUNK_TYPE, &clli[..]),
+            (MasteringDisplayColorVolume::CHUNK_TYPE, &mdcv[..]),
+            (MasteringDisplayColorVolume::LEGACY_CHUNK_TYPE, &mdcv[..]),
+        ] {
+            let chunk = RawChunk {
+                chunk_type,
+                data,
+                offset: 0,
+            };
+            let chunks = AncillaryChunks(
+                parse_ancillary_chunks(vec![chunk], &ihdr, None, &DecodeLimits::default()).unwrap(),
+            );
+            if chunk_type.starts_with('c') {
+                let clli = chunks.get_content_light_level().unwrap();
+                assert_eq!((clli.max_cll, clli.max_fall), (100.0, 20.0));
+            } else {
+                assert!(chunks.get_mastering_display().is_some());
             }
         }
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn chunks_serialize_with_their_type_and_content() {
+        let chunks = AncillaryChunks(vec![
+            AncillaryChunk::tEXt(TextChunk {
+                keyword: "Title".into(),
+                text: "Sunset".into(),
+            }),
+            AncillaryChunk::tIME(Time {
+                year: 2024,
+                month: 5,
+                day: 17,
+                hour: 12,
+                minute: 30,
+                second: 0,
+            }),
+            AncillaryChunk::pHYs(
+                PhysicalUnits::parse(&[0, 0, 0x0b, 0x13, 0, 0, 0x0b, 0x13, 1], 2835, 2835).unwrap(),
+            ),
+        ]);
+        let json = serde_json::to_value(&chunks).unwrap();
+        assert_eq!(
+            json[0],
+            serde_json::json!({
+                "chunk_type": "tEXt",
+                "content": { "keyword": "Title", "text": "Sunset" }
+            })
+        );
+        assert_eq!(json[1]["chunk_type"], "tIME");
+        assert_eq!(json[1]["content"]["year"], 2024);
+        assert_eq!(json[2]["content"]["pixels_per_unit_x"], 2835);
+        assert_eq!(json[2]["content"]["unit_specifier"], "Meter");
+        assert_eq!(json[2]["content"]["actual_width"], 1.0);
+    }
 }
@@ -1,16 +1,17 @@
-use anyhow::Context;
 use nom::{
     number::{complete::u16, Endianness},
     IResult,
 };
 
 use crate::{
-    color_type::{map_pixel_value, ColorType},
+    color_type::{map_pixel_value, unmap_pixel_value, ColorType},
+    error::{PngError, Result},
     plte::Palette,
     run_n,
 };
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Background {
     pub color: (u8, u8, u8),
 }
@@ -18,7 +19,7 @@ pub struct Background {
 impl Background {
     pub const CHUNK_TYPE: &'static str = "bKGD";
 
-    pub fn parse(input: &[u8], color_type: &ColorType, bit_depth: u8) -> anyhow::Result<Self> {
+    pub fn parse(input: &[u8], color_type: &ColorType, bit_depth: u8) -> Result<Self> {
         let mut remaining_input = input;
 
         let mut read_value = || -> IResult<&[u8], u8> {
@@ -36,27 +37,53 @@ impl Background {
 
         let color = match color_type {
             ColorType::Grayscale { .. } | ColorType::GrayscaleAlpha => {
-                let (_, grayscale) = read_value()
-                    .map_err(|e| e.to_owned())
-                    .context("Background parse grayscale")?;
+                let (_, grayscale) = read_value().map_err(|_| {
+                    PngError::ancillary(Self::CHUNK_TYPE, "Background parse grayscale")
+                })?;
                 (grayscale, grayscale, grayscale)
             }
             ColorType::Rgb { .. } | ColorType::Rgba => {
                 run_n!(
                     3,
                     read_value()
-                        .map_err(|e| e.to_owned())
-                        .context("Background parse RGB")?
+                        .map_err(|_| PngError::ancillary(Self::CHUNK_TYPE, "Background parse RGB"))?
                         .1
                 )
             }
             ColorType::Palette(Palette { entries }) => {
-                let index = remaining_input[0] as usize;
-                let pixel = entries[index];
+                let pixel = remaining_input
+                    .first()
+                    .and_then(|&index| entries.get(index as usize))
+                    .ok_or_else(|| {
+                        PngError::ancillary(Self::CHUNK_TYPE, "Background palette index invalid")
+                    })?;
                 (pixel.0, pixel.1, pixel.2)
             }
         };
 
         Ok(Self { color })
     }
+
+    pub fn to_bytes(&self, color_type: &ColorType, bit_depth: u8) -> Result<Vec<u8>> {
+        let (r, g, b) = self.color;
+        let bytes = match color_type {
+            ColorType::Grayscale { .. } | ColorType::GrayscaleAlpha => {
+                unmap_pixel_value(bit_depth, r).to_be_bytes().to_vec()
+            }
+            ColorType::Rgb { .. } | ColorType::Rgba => [r, g, b]
+                .into_iter()
+                .flat_map(|v| unmap_pixel_value(bit_depth, v).to_be_bytes())
+                .collect(),
+            ColorType::Palette(Palette { entries }) => {
+                let index = entries
+                    .iter()
+                    .position(|&(pr, pg, pb, _)| (pr, pg, pb) == self.color)
+                    .ok_or_else(|| {
+                        PngError::InvalidImage("Background color isn't in the palette".to_string())
+                    })?;
+                vec![index as u8]
+            }
+        };
+        Ok(bytes)
+    }
 }
@@ -0,0 +1,140 @@
+use std::fmt;
+
+use nom::{multi::count, number::complete::be_u32, IResult};
+
+use crate::color::{self, Matrix3};
+use crate::error::{PngError, Result};
+
+/// CIE xy chromaticities of the white point and of the primaries
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Chromaticities {
+    pub white_point: (f32, f32),
+    pub red: (f32, f32),
+    pub green: (f32, f32),
+    pub blue: (f32, f32),
+}
+
+impl Chromaticities {
+    pub const CHUNK_TYPE: &'static str = "cHRM";
+
+    /// The chromaticities of sRGB
+    pub const SRGB: Self = Self {
+        white_point: color::D65,
+        red: (0.64, 0.33),
+        green: (0.30, 0.60),
+        blue: (0.15, 0.06),
+    };
+
+    pub fn parse(input: &[u8]) -> Result<Self> {
+        fn parse_nom(input: &[u8]) -> IResult<&[u8], Vec<u32>> {
+            count(be_u32, 8)(input)
+        }
+
+        let (_, values) = parse_nom(input)
+            .map_err(|_| PngError::ancillary(Self::CHUNK_TYPE, "cHRM chunk parsing"))?;
+        let point = |i: usize| (values[i] as f32 / 100000.0, values[i + 1] as f32 / 100000.0);
+        let chromaticities = Self {
+            white_point: point(0),
+            red: point(2),
+            green: point(4),
+            blue: point(6),
+        };
+
+        if chromaticities.to_xyz().is_none() {
+            return Err(PngError::ancillary(
+                Self::CHUNK_TYPE,
+                "cHRM primaries don't describe a color space",
+            ));
+        }
+        Ok(chromaticities)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        [self.white_point, self.red, self.green, self.blue]
+            .iter()
+            .flat_map(|&(x, y)| [x, y])
+            .flat_map(|v| ((v * 100000.0).round() as u32).to_be_bytes())
+            .collect()
+    }
+
+    /// The matrix converting linear RGB in these primaries to CIE XYZ,
+    /// `None` if the chromaticities are degenerate
+    pub fn to_xyz(&self) -> Option<Matrix3> {
+        if [self.white_point, self.red, self.green, self.blue]
+            .iter()
+            .any(|&(_, y)| y <= 0.0)
+        {
+            return None;
+        }
+        let [r, g, b] = [self.red, self.green, self.blue].map(color::xy_to_xyz);
+        let primaries = [[r[0], g[0], b[0]], [r[1], g[1], b[1]], [r[2], g[2], b[2]]];
+        // Scale every primary so that full intensity RGB is the white point
+        let scale = color::apply(
+            &color::invert(&primaries)?,
+            color::xy_to_xyz(self.white_point),
+        );
+        Some(primaries.map(|row| [0, 1, 2].map(|i| row[i] * scale[i])))
+    }
+
+    /// The matrix converting linear RGB in these primaries to linear sRGB
+    pub fn to_srgb(&self) -> Option<Matrix3> {
+        Some(color::multiply(
+            &color::xyz_to_srgb(color::xy_to_xyz(self.white_point)),
+            &self.to_xyz()?,
+        ))
+    }
+}
+
+impl fmt::Display for Chromaticities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "White point: {:?}\nRed: {:?}\nGreen: {:?}\nBlue: {:?}",
+            self.white_point, self.red, self.green, self.blue
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Matrix3, b: Matrix3) {
+        for (row_a, row_b) in a.iter().zip(&b) {
+            for (a, b) in row_a.iter().zip(row_b) {
+                assert!((a - b).abs() < 1e-3, "{:?} != {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn srgb_chromaticities_convert_to_identity() {
+        let chrm = Chromaticities::parse(&Chromaticities::SRGB.to_bytes()).unwrap();
+        assert_eq!(chrm, Chromaticities::SRGB);
+        let identity = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+        assert_close(chrm.to_srgb().unwrap(), identity);
+    }
+
+    #[test]
+    fn white_is_adapted_to_white() {
+        // Adobe RGB primaries with a D50 white point
+        let chrm = Chromaticities {
+            white_point: (0.3457, 0.3585),
+            red: (0.64, 0.33),
+            green: (0.21, 0.71),
+            blue: (0.15, 0.06),
+        };
+        let white = color::apply(&chrm.to_srgb().unwrap(), [1.0; 3]);
+        for v in white {
+            assert!((v - 1.0).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn degenerate_primaries_are_rejected() {
+        let mut chrm = Chromaticities::SRGB;
+        chrm.green = chrm.red;
+        assert!(Chromaticities::parse(&chrm.to_bytes()).is_err());
+    }
+}
@@ -0,0 +1,133 @@
+use std::fmt;
+
+use crate::ancillary_chunks::chrm::Chromaticities;
+use crate::error::{PngError, Result};
+
+/// Transfer functions of high dynamic range content
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HdrTransfer {
+    /// SMPTE ST 2084 perceptual quantizer
+    Pq,
+    /// ARIB STD-B67 hybrid log-gamma
+    Hlg,
+}
+
+/// Coding-independent code points of ITU-T H.273 identifying the color space
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CodingIndependentCodePoints {
+    pub color_primaries: u8,
+    pub transfer_function: u8,
+    /// Always 0, PNG images are RGB
+    pub matrix_coefficients: u8,
+    pub full_range: bool,
+}
+
+impl CodingIndependentCodePoints {
+    pub const CHUNK_TYPE: &'static str = "cICP";
+
+    pub fn parse(content: &[u8]) -> Result<Self> {
+        let &[color_primaries, transfer_function, matrix_coefficients, full_range] = content else {
+            return Err(PngError::ancillary(
+                Self::CHUNK_TYPE,
+                "Invalid cICP chunk content",
+            ));
+        };
+        if matrix_coefficients != 0 {
+            return Err(PngError::ancillary(
+                Self::CHUNK_TYPE,
+                format!("Matrix coefficients must be 0, not {}", matrix_coefficients),
+            ));
+        }
+        let full_range = match full_range {
+            0 => false,
+            1 => true,
+            value => {
+                return Err(PngError::ancillary(
+                    Self::CHUNK_TYPE,
+                    format!("Invalid full range flag: {}", value),
+                ))
+            }
+        };
+        Ok(Self {
+            color_primaries,
+            transfer_function,
+            matrix_coefficients,
+            full_range,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        vec![
+            self.color_primaries,
+            self.transfer_function,
+            self.matrix_coefficients,
+            self.full_range as u8,
+        ]
+    }
+
+    /// The chromaticities of the common primaries, BT.709, BT.2020 and Display P3
+    pub fn primaries(&self) -> Option<Chromaticities> {
+        let white_point = Chromaticities::SRGB.white_point;
+        match self.color_primaries {
+            1 => Some(Chromaticities::SRGB),
+            9 => Some(Chromaticities {
+                white_point,
+                red: (0.708, 0.292),
+                green: (0.170, 0.797),
+                blue: (0.131, 0.046),
+            }),
+            12 => Some(Chromaticities {
+                white_point,
+                red: (0.680, 0.320),
+                green: (0.265, 0.690),
+                blue: (0.150, 0.060),
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn hdr_transfer(&self) -> Option<HdrTransfer> {
+        match self.transfer_function {
+            16 => Some(HdrTransfer::Pq),
+            18 => Some(HdrTransfer::Hlg),
+            _ => None,
+        }
+    }
+}
+
+fn primaries_name(value: u8) -> &'static str {
+    match value {
+        1 => "BT.709",
+        9 => "BT.2020",
+        11 => "DCI-P3",
+        12 => "Display P3",
+        _ => "other",
+    }
+}
+
+fn transfer_name(value: u8) -> &'static str {
+    match value {
+        1 | 6 | 14 | 15 => "BT.709",
+        8 => "linear",
+        13 => "sRGB",
+        16 => "PQ",
+        18 => "HLG",
+        _ => "other",
+    }
+}
+
+impl fmt::Display for CodingIndependentCodePoints {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Color primaries: {} ({})\nTransfer function: {} ({})\nMatrix coefficients: {}\nFull range: {}",
+            self.color_primaries,
+            primaries_name(self.color_primaries),
+            self.transfer_function,
+            transfer_name(self.transfer_function),
+            self.matrix_coefficients,
+            self.full_range
+        )
+    }
+}
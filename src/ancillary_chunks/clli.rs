@@ -0,0 +1,59 @@
+use std::fmt;
+
+use nom::{number::complete::be_u32, IResult};
+
+use crate::error::{PngError, Result};
+
+/// Light levels of the brightest pixel and of the brightest frame on average
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ContentLightLevel {
+    /// Maximum content light level in cd/m²
+    pub max_cll: f32,
+    /// Maximum frame-average light level in cd/m²
+    pub max_fall: f32,
+}
+
+impl ContentLightLevel {
+    pub const CHUNK_TYPE: &'static str = "cLLI";
+    /// Name from the drafts of the third edition, still written by some encoders
+    pub const LEGACY_CHUNK_TYPE: &'static str = "cLLi";
+
+    pub fn parse(input: &[u8]) -> Result<Self> {
+        fn parse_nom(input: &[u8]) -> IResult<&[u8], (u32, u32)> {
+            let (input, max_cll) = be_u32(input)?;
+            let (input, max_fall) = be_u32(input)?;
+            Ok((input, (max_cll, max_fall)))
+        }
+
+        if input.len() != 8 {
+            return Err(PngError::ancillary(
+                Self::CHUNK_TYPE,
+                "Invalid cLLI chunk length",
+            ));
+        }
+        let (_, (max_cll, max_fall)) = parse_nom(input)
+            .map_err(|_| PngError::ancillary(Self::CHUNK_TYPE, "cLLI chunk parsing"))?;
+        Ok(Self {
+            max_cll: max_cll as f32 * 0.0001,
+            max_fall: max_fall as f32 * 0.0001,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        [self.max_cll, self.max_fall]
+            .iter()
+            .flat_map(|v| ((v / 0.0001).round() as u32).to_be_bytes())
+            .collect()
+    }
+}
+
+impl fmt::Display for ContentLightLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Max content light level: {} cd/m²\nMax frame-average light level: {} cd/m²",
+            self.max_cll, self.max_fall
+        )
+    }
+}
@@ -0,0 +1,554 @@
+use std::fmt;
+
+use nom::{
+    number::{
+        complete::{i32 as nom_i32, u16 as nom_u16, u32 as nom_u32},
+        Endianness,
+    },
+    IResult,
+};
+
+use crate::error::{PngError, Result};
+use crate::image_buffer::ImageBuffer;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum ByteOrder {
+    LittleEndian,
+    BigEndian,
+}
+
+/// The image file directory an entry was read from
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum Ifd {
+    Primary,
+    Exif,
+    Gps,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum ExifValue {
+    /// BYTE, SBYTE and UNDEFINED values
+    Bytes(Vec<u8>),
+    Ascii(String),
+    Short(Vec<u16>),
+    Long(Vec<u32>),
+    Rational(Vec<(u32, u32)>),
+    SignedLong(Vec<i32>),
+    SignedRational(Vec<(i32, i32)>),
+}
+
+impl ExifValue {
+    /// The first value as an integer
+    pub fn as_u32(&self) -> Option<u32> {
+        match self {
+            ExifValue::Bytes(v) => v.first().map(|&v| v as u32),
+            ExifValue::Short(v) => v.first().map(|&v| v as u32),
+            ExifValue::Long(v) => v.first().copied(),
+            _ => None,
+        }
+    }
+
+    /// Every value as a float, rationals are divided out
+    pub fn as_f64s(&self) -> Vec<f64> {
+        match self {
+            ExifValue::Bytes(v) => v.iter().map(|&v| v as f64).collect(),
+            ExifValue::Ascii(_) => Vec::new(),
+            ExifValue::Short(v) => v.iter().map(|&v| v as f64).collect(),
+            ExifValue::Long(v) => v.iter().map(|&v| v as f64).collect(),
+            ExifValue::Rational(v) => v.iter().map(|&(n, d)| n as f64 / d as f64).collect(),
+            ExifValue::SignedLong(v) => v.iter().map(|&v| v as f64).collect(),
+            ExifValue::SignedRational(v) => v.iter().map(|&(n, d)| n as f64 / d as f64).collect(),
+        }
+    }
+}
+
+impl fmt::Display for ExifValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExifValue::Ascii(s) => write!(f, "{}", s),
+            ExifValue::Bytes(v) if v.len() > 16 => write!(f, "{} bytes", v.len()),
+            ExifValue::Rational(v) if v.len() == 1 => write!(f, "{}/{}", v[0].0, v[0].1),
+            ExifValue::SignedRational(v) if v.len() == 1 => write!(f, "{}/{}", v[0].0, v[0].1),
+            _ => {
+                let values = self.as_f64s();
+                match values[..] {
+                    [value] => write!(f, "{}", value),
+                    _ => write!(f, "{:?}", values),
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ExifEntry {
+    pub ifd: Ifd,
+    pub tag: u16,
+    pub value: ExifValue,
+}
+
+/// How the stored image has to be transformed to be displayed upright
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Orientation {
+    TopLeft,
+    TopRight,
+    BottomRight,
+    BottomLeft,
+    LeftTop,
+    RightTop,
+    RightBottom,
+    LeftBottom,
+}
+
+impl Orientation {
+    fn from_u32(value: u32) -> Option<Self> {
+        match value {
+            1 => Some(Self::TopLeft),
+            2 => Some(Self::TopRight),
+            3 => Some(Self::BottomRight),
+            4 => Some(Self::BottomLeft),
+            5 => Some(Self::LeftTop),
+            6 => Some(Self::RightTop),
+            7 => Some(Self::RightBottom),
+            8 => Some(Self::LeftBottom),
+            _ => None,
+        }
+    }
+
+    /// The displayed image is rotated by 90 degrees, width and height are exchanged
+    pub fn swaps_dimensions(&self) -> bool {
+        matches!(
+            self,
+            Self::LeftTop | Self::RightTop | Self::RightBottom | Self::LeftBottom
+        )
+    }
+
+    /// Returns the image as it should be displayed
+    pub fn apply<S: Copy + Default>(&self, image: &ImageBuffer<S>) -> ImageBuffer<S> {
+        let (width, height) = (image.width(), image.height());
+        let mut oriented = if self.swaps_dimensions() {
+            ImageBuffer::new(height, width)
+        } else {
+            ImageBuffer::new(width, height)
+        };
+        for y in 0..height {
+            for x in 0..width {
+                let (right, bottom) = (width - 1 - x, height - 1 - y);
+                let (new_x, new_y) = match self {
+                    Self::TopLeft => (x, y),
+                    Self::TopRight => (right, y),
+                    Self::BottomRight => (right, bottom),
+                    Self::BottomLeft => (x, bottom),
+                    Self::LeftTop => (y, x),
+                    Self::RightTop => (bottom, x),
+                    Self::RightBottom => (bottom, right),
+                    Self::LeftBottom => (y, right),
+                };
+                oriented.set_pixel(new_x, new_y, image.pixel(x, y));
+            }
+        }
+        oriented
+    }
+}
+
+/// Position in degrees, south and west are negative
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GpsPosition {
+    pub latitude: f64,
+    pub longitude: f64,
+    /// Meters above sea level
+    pub altitude: Option<f64>,
+}
+
+const MAKE: u16 = 0x010f;
+const MODEL: u16 = 0x0110;
+const ORIENTATION: u16 = 0x0112;
+const DATE_TIME: u16 = 0x0132;
+const EXIF_IFD: u16 = 0x8769;
+const GPS_IFD: u16 = 0x8825;
+const DATE_TIME_ORIGINAL: u16 = 0x9003;
+const GPS_LATITUDE_REF: u16 = 0x0001;
+const GPS_LATITUDE: u16 = 0x0002;
+const GPS_LONGITUDE_REF: u16 = 0x0003;
+const GPS_LONGITUDE: u16 = 0x0004;
+const GPS_ALTITUDE_REF: u16 = 0x0005;
+const GPS_ALTITUDE: u16 = 0x0006;
+
+fn tag_name(ifd: Ifd, tag: u16) -> Option<&'static str> {
+    Some(match (ifd, tag) {
+        (Ifd::Gps, GPS_LATITUDE_REF) => "GPS latitude ref",
+        (Ifd::Gps, GPS_LATITUDE) => "GPS latitude",
+        (Ifd::Gps, GPS_LONGITUDE_REF) => "GPS longitude ref",
+        (Ifd::Gps, GPS_LONGITUDE) => "GPS longitude",
+        (Ifd::Gps, GPS_ALTITUDE_REF) => "GPS altitude ref",
+        (Ifd::Gps, GPS_ALTITUDE) => "GPS altitude",
+        (Ifd::Gps, _) => return None,
+        (_, MAKE) => "Make",
+        (_, MODEL) => "Model",
+        (_, ORIENTATION) => "Orientation",
+        (_, 0x0131) => "Software",
+        (_, DATE_TIME) => "Date time",
+        (_, 0x013b) => "Artist",
+        (_, 0x8298) => "Copyright",
+        (_, 0x829a) => "Exposure time",
+        (_, 0x829d) => "F number",
+        (_, 0x8827) => "ISO",
+        (_, DATE_TIME_ORIGINAL) => "Date time original",
+        (_, 0x920a) => "Focal length",
+        (_, 0xa433) => "Lens make",
+        (_, 0xa434) => "Lens model",
+        _ => return None,
+    })
+}
+
+/// Reads values at absolute offsets of the TIFF structure
+struct TiffReader<'a> {
+    data: &'a [u8],
+    endianness: Endianness,
+}
+
+impl<'a> TiffReader<'a> {
+    fn at(&self, offset: usize) -> Option<&'a [u8]> {
+        self.data.get(offset..)
+    }
+
+    fn u16(&self, input: &'a [u8]) -> IResult<&'a [u8], u16> {
+        nom_u16(self.endianness)(input)
+    }
+
+    fn u32(&self, input: &'a [u8]) -> IResult<&'a [u8], u32> {
+        nom_u32(self.endianness)(input)
+    }
+
+    fn i32(&self, input: &'a [u8]) -> IResult<&'a [u8], i32> {
+        nom_i32(self.endianness)(input)
+    }
+
+    /// Reads `count` values of `size` bytes each
+    fn values<T>(
+        &self,
+        input: &'a [u8],
+        count: usize,
+        size: usize,
+        read: impl Fn(&'a [u8]) -> IResult<&'a [u8], T>,
+    ) -> Option<Vec<T>> {
+        let bytes = input.get(..size.checked_mul(count)?)?;
+        bytes
+            .chunks_exact(size)
+            .map(|v| read(v).ok().map(|(_, v)| v))
+            .collect()
+    }
+
+    /// Reads `count` values of the TIFF `field_type`, `None` for unsupported types
+    fn value(&self, field_type: u16, count: usize, input: &'a [u8]) -> Option<ExifValue> {
+        let rational = |input| {
+            let (input, numerator) = self.u32(input)?;
+            let (input, denominator) = self.u32(input)?;
+            Ok((input, (numerator, denominator)))
+        };
+        let signed_rational = |input| {
+            let (input, numerator) = self.i32(input)?;
+            let (input, denominator) = self.i32(input)?;
+            Ok((input, (numerator, denominator)))
+        };
+        Some(match field_type {
+            1 | 6 | 7 => ExifValue::Bytes(input.get(..count)?.to_vec()),
+            2 => {
+                let text = input.get(..count)?;
+                let text = text.split(|&b| b == 0).next().unwrap_or_default();
+                ExifValue::Ascii(String::from_utf8_lossy(text).trim_end().to_string())
+            }
+            3 => ExifValue::Short(self.values(input, count, 2, |v| self.u16(v))?),
+            4 => ExifValue::Long(self.values(input, count, 4, |v| self.u32(v))?),
+            5 => ExifValue::Rational(self.values(input, count, 8, rational)?),
+            9 => ExifValue::SignedLong(self.values(input, count, 4, |v| self.i32(v))?),
+            10 => ExifValue::SignedRational(self.values(input, count, 8, signed_rational)?),
+            _ => return None,
+        })
+    }
+
+    /// Reads the entries of the directory at `offset`, entries of unsupported types are skipped
+    fn ifd(&self, offset: usize, ifd: Ifd) -> Option<Vec<ExifEntry>> {
+        let (mut input, count) = self.u16(self.at(offset)?).ok()?;
+        let mut entries = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let (rest, tag) = self.u16(input).ok()?;
+            let (rest, field_type) = self.u16(rest).ok()?;
+            let (rest, value_count) = self.u32(rest).ok()?;
+            let size = match field_type {
+                1 | 2 | 6 | 7 => 1,
+                3 | 8 => 2,
+                4 | 9 | 11 => 4,
+                _ => 8,
+            };
+            // Values of up to 4 bytes are stored in the entry itself
+            let value_input = if size * value_count as u64 <= 4 {
+                rest
+            } else {
+                self.at(self.u32(rest).ok()?.1 as usize)?
+            };
+            if let Some(value) = self.value(field_type, value_count as usize, value_input) {
+                entries.push(ExifEntry { ifd, tag, value });
+            }
+            input = rest.get(4..)?;
+        }
+        Some(entries)
+    }
+}
+
+/// EXIF metadata, stored as a TIFF structure
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Exif<'a> {
+    pub byte_order: ByteOrder,
+    /// Entries of the primary image directory and of its EXIF and GPS directories
+    pub entries: Vec<ExifEntry>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    data: &'a [u8],
+}
+
+impl<'a> Exif<'a> {
+    pub const CHUNK_TYPE: &'static str = "eXIf";
+
+    pub fn parse(data: &'a [u8]) -> Result<Self> {
+        let invalid = |reason: &str| PngError::ancillary(Self::CHUNK_TYPE, reason);
+        let byte_order = match data.get(..4) {
+            Some(b"II*\0") => ByteOrder::LittleEndian,
+            Some(b"MM\0*") => ByteOrder::BigEndian,
+            _ => return Err(invalid("Invalid TIFF header")),
+        };
+        let reader = TiffReader {
+            data,
+            endianness: match byte_order {
+                ByteOrder::LittleEndian => Endianness::Little,
+                ByteOrder::BigEndian => Endianness::Big,
+            },
+        };
+        let (_, ifd_offset) = reader
+            .u32(&data[4..])
+            .map_err(|_| invalid("Invalid TIFF header"))?;
+
+        let mut entries = reader
+            .ifd(ifd_offset as usize, Ifd::Primary)
+            .ok_or_else(|| invalid("Invalid primary image directory"))?;
+        // Only the directories the primary one points to are read, so offsets can't loop
+        let pointers = entries
+            .iter()
+            .filter_map(|entry| match entry.tag {
+                EXIF_IFD => Some((Ifd::Exif, entry.value.as_u32()?)),
+                GPS_IFD => Some((Ifd::Gps, entry.value.as_u32()?)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        for (ifd, offset) in pointers {
+            let directory = reader
+                .ifd(offset as usize, ifd)
+                .ok_or_else(|| invalid("Invalid EXIF or GPS directory"))?;
+            entries.extend(directory);
+        }
+
+        Ok(Self {
+            byte_order,
+            entries,
+            data,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.data.to_vec()
+    }
+
+    pub fn get(&self, ifd: Ifd, tag: u16) -> Option<&ExifValue> {
+        self.entries
+            .iter()
+            .find(|entry| entry.ifd == ifd && entry.tag == tag)
+            .map(|entry| &entry.value)
+    }
+
+    fn ascii(&self, ifd: Ifd, tag: u16) -> Option<&str> {
+        match self.get(ifd, tag)? {
+            ExifValue::Ascii(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn orientation(&self) -> Option<Orientation> {
+        Orientation::from_u32(self.get(Ifd::Primary, ORIENTATION)?.as_u32()?)
+    }
+
+    pub fn make(&self) -> Option<&str> {
+        self.ascii(Ifd::Primary, MAKE)
+    }
+
+    pub fn model(&self) -> Option<&str> {
+        self.ascii(Ifd::Primary, MODEL)
+    }
+
+    /// When the picture was taken, falling back to when the file was last changed
+    pub fn date_time(&self) -> Option<&str> {
+        self.ascii(Ifd::Exif, DATE_TIME_ORIGINAL)
+            .or_else(|| self.ascii(Ifd::Primary, DATE_TIME))
+    }
+
+    pub fn gps(&self) -> Option<GpsPosition> {
+        let degrees = |tag, ref_tag, negative| {
+            let value = self.get(Ifd::Gps, tag)?.as_f64s();
+            let [degrees, minutes, seconds] = value[..] else {
+                return None;
+            };
+            let sign = match self.ascii(Ifd::Gps, ref_tag) {
+                Some(r) if r == negative => -1.0,
+                _ => 1.0,
+            };
+            Some(sign * (degrees + minutes / 60.0 + seconds / 3600.0))
+        };
+        let altitude = self.get(Ifd::Gps, GPS_ALTITUDE).and_then(|altitude| {
+            let below_sea_level = self
+                .get(Ifd::Gps, GPS_ALTITUDE_REF)
+                .and_then(ExifValue::as_u32)
+                == Some(1);
+            let altitude = *altitude.as_f64s().first()?;
+            Some(if below_sea_level { -altitude } else { altitude })
+        });
+        Some(GpsPosition {
+            latitude: degrees(GPS_LATITUDE, GPS_LATITUDE_REF, "S")?,
+            longitude: degrees(GPS_LONGITUDE, GPS_LONGITUDE_REF, "W")?,
+            altitude,
+        })
+    }
+}
+
+impl fmt::Display for Exif<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Byte order: {:?}", self.byte_order)?;
+        if let Some(orientation) = self.orientation() {
+            write!(f, "\nOrientation: {:?}", orientation)?;
+        }
+        if let Some(gps) = self.gps() {
+            write!(
+                f,
+                "\nGPS position: {:.6}, {:.6}",
+                gps.latitude, gps.longitude
+            )?;
+            if let Some(altitude) = gps.altitude {
+                write!(f, ", {:.1} m", altitude)?;
+            }
+        }
+        let mut unnamed = 0;
+        for entry in &self.entries {
+            match tag_name(entry.ifd, entry.tag) {
+                Some(_) if entry.ifd == Ifd::Gps || entry.tag == ORIENTATION => {}
+                None if matches!(entry.tag, EXIF_IFD | GPS_IFD) => {}
+                Some(name) => write!(f, "\n{}: {}", name, entry.value)?,
+                None => unnamed += 1,
+            }
+        }
+        if unnamed > 0 {
+            write!(f, "\nOther tags: {}", unnamed)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A TIFF structure with an orientation of 6 and a GPS directory at 48°30'N 2°15'W
+    fn tiff(byte_order: ByteOrder) -> Vec<u8> {
+        let mut data = Vec::new();
+        let u16 = |data: &mut Vec<u8>, v: u16| match byte_order {
+            ByteOrder::LittleEndian => data.extend(v.to_le_bytes()),
+            ByteOrder::BigEndian => data.extend(v.to_be_bytes()),
+        };
+        let u32 = |data: &mut Vec<u8>, v: u32| match byte_order {
+            ByteOrder::LittleEndian => data.extend(v.to_le_bytes()),
+            ByteOrder::BigEndian => data.extend(v.to_be_bytes()),
+        };
+        let entry = |data: &mut Vec<u8>, tag, field_type, count| {
+            u16(data, tag);
+            u16(data, field_type);
+            u32(data, count);
+        };
+        data.extend(match byte_order {
+            ByteOrder::LittleEndian => b"II*\0",
+            ByteOrder::BigEndian => b"MM\0*",
+        });
+        u32(&mut data, 8);
+
+        // Primary directory at 8, 30 bytes long
+        u16(&mut data, 2);
+        entry(&mut data, ORIENTATION, 3, 1);
+        u16(&mut data, 6);
+        u16(&mut data, 0);
+        entry(&mut data, GPS_IFD, 4, 1);
+        u32(&mut data, 38);
+        u32(&mut data, 0);
+
+        // GPS directory at 38, 54 bytes long, followed by the rationals
+        u16(&mut data, 4);
+        entry(&mut data, GPS_LATITUDE_REF, 2, 2);
+        data.extend(b"N\0\0\0");
+        entry(&mut data, GPS_LATITUDE, 5, 3);
+        u32(&mut data, 92);
+        entry(&mut data, GPS_LONGITUDE_REF, 2, 2);
+        data.extend(b"W\0\0\0");
+        entry(&mut data, GPS_LONGITUDE, 5, 3);
+        u32(&mut data, 116);
+        u32(&mut data, 0);
+        for value in [48, 30, 0, 2, 15, 0] {
+            u32(&mut data, value);
+            u32(&mut data, 1);
+        }
+        data
+    }
+
+    #[test]
+    fn both_byte_orders_read_the_same_tags() {
+        for byte_order in [ByteOrder::LittleEndian, ByteOrder::BigEndian] {
+            let data = tiff(byte_order);
+            let exif = Exif::parse(&data).unwrap();
+            assert_eq!(exif.byte_order, byte_order);
+            assert_eq!(exif.orientation(), Some(Orientation::RightTop));
+            assert_eq!(
+                exif.gps(),
+                Some(GpsPosition {
+                    latitude: 48.5,
+                    longitude: -2.25,
+                    altitude: None,
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_headers_are_rejected() {
+        assert!(Exif::parse(b"II+\0\x08\0\0\0").is_err());
+        let mut data = tiff(ByteOrder::BigEndian);
+        data.truncate(20);
+        assert!(Exif::parse(&data).is_err());
+    }
+
+    #[test]
+    fn orientation_rotates_and_flips() {
+        // A 2x1 image with a red and a green pixel
+        let image = ImageBuffer::from_samples(2, 1, vec![1, 0, 0, 1, 0, 1, 0, 1]).unwrap();
+
+        let rotated = Orientation::RightTop.apply(&image);
+        assert_eq!((rotated.width(), rotated.height()), (1, 2));
+        assert_eq!(rotated.pixel(0, 0), (1, 0, 0, 1));
+        assert_eq!(rotated.pixel(0, 1), (0, 1, 0, 1));
+
+        let mirrored = Orientation::TopRight.apply(&image);
+        assert_eq!((mirrored.width(), mirrored.height()), (2, 1));
+        assert_eq!(mirrored.pixel(0, 0), (0, 1, 0, 1));
+
+        let rotated = Orientation::LeftBottom.apply(&image);
+        assert_eq!(rotated.pixel(0, 0), (0, 1, 0, 1));
+        assert_eq!(rotated.pixel(0, 1), (1, 0, 0, 1));
+    }
+}
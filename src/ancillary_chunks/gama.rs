@@ -1,19 +1,29 @@
 use core::fmt;
 
+use crate::error::{PngError, Result};
+
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Gama(pub f32);
 
 impl Gama {
     pub const CHUNK_TYPE: &'static str = "gAMA";
 
-    pub fn parse(content: &[u8]) -> anyhow::Result<Self> {
+    pub fn parse(content: &[u8]) -> Result<Self> {
         if content.len() != 4 {
-            anyhow::bail!("Invalid gama chunk content");
+            return Err(PngError::ancillary(
+                Self::CHUNK_TYPE,
+                "Invalid gama chunk content",
+            ));
         }
 
         let value = u32::from_be_bytes(content.try_into().expect("content is len 4")) as f32;
         Ok(Self(value / 100000.0))
     }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        ((self.0 * 100000.0).round() as u32).to_be_bytes().to_vec()
+    }
 }
 
 impl fmt::Display for Gama {
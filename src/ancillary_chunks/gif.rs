@@ -0,0 +1,123 @@
+use std::{borrow::Cow, fmt, time::Duration};
+
+use crate::error::{PngError, Result};
+
+use super::text::{iso_8859_1_to_string, string_to_iso_8859_1};
+
+/// The Graphic Control Extension of the GIF the image was converted from
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct GifGraphicControl {
+    pub disposal_method: u8,
+    /// The viewer should wait for user input before going on
+    pub user_input: bool,
+    /// In hundredths of a second
+    pub delay_time: u16,
+}
+
+impl GifGraphicControl {
+    pub const CHUNK_TYPE: &'static str = "gIFg";
+
+    pub fn parse(content: &[u8]) -> Result<Self> {
+        let &[disposal_method, user_input, delay_high, delay_low] = content else {
+            return Err(PngError::ancillary(
+                Self::CHUNK_TYPE,
+                "Invalid gIFg chunk content",
+            ));
+        };
+        Ok(Self {
+            disposal_method,
+            user_input: user_input != 0,
+            delay_time: u16::from_be_bytes([delay_high, delay_low]),
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![self.disposal_method, self.user_input as u8];
+        bytes.extend_from_slice(&self.delay_time.to_be_bytes());
+        bytes
+    }
+
+    pub fn delay(&self) -> Duration {
+        Duration::from_millis(self.delay_time as u64 * 10)
+    }
+}
+
+impl fmt::Display for GifGraphicControl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Disposal method: {}\nUser input: {}\nDelay: {:?}",
+            self.disposal_method,
+            self.user_input,
+            self.delay()
+        )
+    }
+}
+
+/// An Application Extension of the GIF the image was converted from
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct GifApplicationExtension<'a> {
+    pub identifier: Cow<'a, str>,
+    pub authentication_code: [u8; 3],
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "length", serialize_with = "crate::chunk::serialize_len")
+    )]
+    pub data: &'a [u8],
+}
+
+impl<'a> GifApplicationExtension<'a> {
+    pub const CHUNK_TYPE: &'static str = "gIFx";
+
+    pub fn parse(content: &'a [u8]) -> Result<Self> {
+        if content.len() < 11 {
+            return Err(PngError::ancillary(
+                Self::CHUNK_TYPE,
+                "Invalid gIFx chunk length",
+            ));
+        }
+        let (identifier, rest) = content.split_at(8);
+        let (authentication_code, data) = rest.split_at(3);
+        Ok(Self {
+            identifier: iso_8859_1_to_string(identifier),
+            authentication_code: authentication_code.try_into().expect("Slice len validated"),
+            data,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = string_to_iso_8859_1(&self.identifier);
+        bytes.extend_from_slice(&self.authentication_code);
+        bytes.extend_from_slice(self.data);
+        bytes
+    }
+}
+
+impl fmt::Display for GifApplicationExtension<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Application: {}\nAuthentication code: {:?}\nData: {} bytes",
+            self.identifier,
+            self.authentication_code,
+            self.data.len()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn graphic_control_delay_is_in_hundredths() {
+        let control = GifGraphicControl::parse(&[2, 1, 0x01, 0x2c]).unwrap();
+        assert_eq!(control.disposal_method, 2);
+        assert!(control.user_input);
+        assert_eq!(control.delay(), Duration::from_secs(3));
+        assert_eq!(control.to_bytes(), [2, 1, 0x01, 0x2c]);
+        assert!(GifGraphicControl::parse(&[2, 1, 0x01]).is_err());
+    }
+}
@@ -0,0 +1,81 @@
+use std::fmt;
+
+use nom::{multi::many0, number::complete::be_u16, IResult};
+
+use crate::error::{PngError, Result};
+
+/// Approximate usage frequency of every palette entry
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Histogram {
+    pub frequencies: Vec<u16>,
+}
+
+impl Histogram {
+    pub const CHUNK_TYPE: &'static str = "hIST";
+
+    /// There must be one frequency per entry of the PLTE chunk, which is the suggested palette
+    /// of truecolor images
+    pub fn parse(input: &[u8], palette_len: Option<usize>) -> Result<Self> {
+        fn parse_nom(input: &[u8]) -> IResult<&[u8], Vec<u16>> {
+            many0(be_u16)(input)
+        }
+
+        if !input.len().is_multiple_of(2) {
+            return Err(PngError::ancillary(
+                Self::CHUNK_TYPE,
+                "Invalid hIST chunk length",
+            ));
+        }
+        let (_, frequencies) = parse_nom(input)
+            .map_err(|_| PngError::ancillary(Self::CHUNK_TYPE, "hIST chunk parsing"))?;
+
+        match palette_len {
+            None => {
+                return Err(PngError::ancillary(
+                    Self::CHUNK_TYPE,
+                    "hIST without a PLTE chunk",
+                ))
+            }
+            Some(palette_len) if palette_len != frequencies.len() => {
+                return Err(PngError::ancillary(
+                    Self::CHUNK_TYPE,
+                    format!(
+                        "hIST has {} entries but the palette has {}",
+                        frequencies.len(),
+                        palette_len
+                    ),
+                ))
+            }
+            Some(_) => {}
+        }
+        Ok(Self { frequencies })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.frequencies
+            .iter()
+            .flat_map(|f| f.to_be_bytes())
+            .collect()
+    }
+}
+
+impl fmt::Display for Histogram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Frequencies: {:?}", self.frequencies)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_frequency_per_palette_entry() {
+        let input = [0, 1, 0, 2, 0, 3];
+        let histogram = Histogram::parse(&input, Some(3)).unwrap();
+        assert_eq!(histogram.frequencies, [1, 2, 3]);
+        assert!(Histogram::parse(&input, Some(4)).is_err());
+        assert!(Histogram::parse(&input, None).is_err());
+    }
+}
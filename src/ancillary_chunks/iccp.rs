@@ -0,0 +1,112 @@
+use std::{borrow::Cow, fmt};
+
+use nom::{bytes::complete::take_until, number::complete::u8, IResult};
+
+use crate::error::{PngError, Result};
+use crate::icc::{IccProfile, IccTransform};
+use crate::ihdr::CompressionMethod;
+use crate::limits::DecodeLimits;
+use crate::png_parser::inflate_chunk;
+
+use super::text::{iso_8859_1_to_string, string_to_iso_8859_1};
+
+/// An embedded ICC profile describing the color space of the samples
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct IccProfileChunk<'a> {
+    pub name: Cow<'a, str>,
+    /// The decompressed profile
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "length", serialize_with = "crate::chunk::serialize_len")
+    )]
+    pub profile: Vec<u8>,
+}
+
+impl<'a> IccProfileChunk<'a> {
+    pub const CHUNK_TYPE: &'static str = "iCCP";
+
+    pub fn parse(input: &'a [u8], limits: &DecodeLimits) -> Result<Self> {
+        fn parse_nom(input: &[u8]) -> IResult<&[u8], (&[u8], u8)> {
+            let (input, name) = take_until(&[0][..])(input)?;
+            let (input, _) = u8(input)?;
+            let (input, compression_method) = u8(input)?;
+            Ok((input, (name, compression_method)))
+        }
+
+        let (compressed, (name, compression_method)) = parse_nom(input)
+            .map_err(|_| PngError::ancillary(Self::CHUNK_TYPE, "iCCP chunk parsing"))?;
+        if name.is_empty() || name.len() > 79 {
+            return Err(PngError::ancillary(
+                Self::CHUNK_TYPE,
+                "Profile name must be 1 to 79 bytes long",
+            ));
+        }
+        let CompressionMethod::Zlib =
+            CompressionMethod::from_u8(compression_method).ok_or_else(|| {
+                PngError::ancillary(Self::CHUNK_TYPE, "Compression method value iCCP")
+            })?;
+
+        let profile = inflate_chunk(
+            compressed,
+            Self::CHUNK_TYPE,
+            "inflated bytes",
+            limits.max_inflated_bytes,
+        )?;
+        // The profile itself is only parsed when it's shown or applied
+        Ok(Self {
+            name: iso_8859_1_to_string(name),
+            profile,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = string_to_iso_8859_1(&self.name);
+        bytes.push(0);
+        bytes.push(CompressionMethod::Zlib.to_u8());
+        bytes.extend(deflate::deflate_bytes_zlib(&self.profile));
+        bytes
+    }
+
+    /// The header and tag table of the profile
+    pub fn parse_profile(&self) -> Result<IccProfile<'_>> {
+        IccProfile::parse(&self.profile)
+    }
+
+    /// The conversion to sRGB, only matrix/TRC RGB and gray TRC profiles are supported
+    pub fn transform(&self) -> Result<IccTransform> {
+        IccTransform::new(&self.parse_profile()?)
+    }
+}
+
+impl fmt::Display for IccProfileChunk<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Profile name: {}", self.name)?;
+        write!(f, "Profile size: {} bytes", self.profile.len())?;
+        if let Ok(profile) = self.parse_profile() {
+            write!(f, "\n{}", profile)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profile_is_only_checked_when_applied() {
+        let mut input = b"broken\0\0".to_vec();
+        input.extend(deflate::deflate_bytes_zlib(b"not a profile"));
+        let chunk = IccProfileChunk::parse(&input, &DecodeLimits::default()).unwrap();
+        assert_eq!(chunk.name, "broken");
+        assert_eq!(chunk.profile, b"not a profile");
+        assert!(matches!(chunk.transform(), Err(PngError::IccProfile(_))));
+    }
+
+    #[test]
+    fn corrupt_compressed_data_is_rejected() {
+        let input = b"broken\0\0\x78\x9c\xff\xff";
+        assert!(IccProfileChunk::parse(input, &DecodeLimits::default()).is_err());
+    }
+}
@@ -0,0 +1,83 @@
+use std::fmt;
+
+use nom::{
+    multi::count,
+    number::complete::{be_u16, be_u32},
+    IResult,
+};
+
+use crate::error::{PngError, Result};
+
+/// Color volume of the display the content was mastered on
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MasteringDisplayColorVolume {
+    pub red: (f32, f32),
+    pub green: (f32, f32),
+    pub blue: (f32, f32),
+    pub white_point: (f32, f32),
+    /// In cd/m²
+    pub max_luminance: f32,
+    /// In cd/m²
+    pub min_luminance: f32,
+}
+
+impl MasteringDisplayColorVolume {
+    pub const CHUNK_TYPE: &'static str = "mDCV";
+    /// Name from the drafts of the third edition, still written by some encoders
+    pub const LEGACY_CHUNK_TYPE: &'static str = "mDCv";
+
+    pub fn parse(input: &[u8]) -> Result<Self> {
+        fn parse_nom(input: &[u8]) -> IResult<&[u8], (Vec<u16>, u32, u32)> {
+            let (input, chromaticities) = count(be_u16, 8)(input)?;
+            let (input, max_luminance) = be_u32(input)?;
+            let (input, min_luminance) = be_u32(input)?;
+            Ok((input, (chromaticities, max_luminance, min_luminance)))
+        }
+
+        if input.len() != 24 {
+            return Err(PngError::ancillary(
+                Self::CHUNK_TYPE,
+                "Invalid mDCV chunk length",
+            ));
+        }
+        let (_, (values, max_luminance, min_luminance)) = parse_nom(input)
+            .map_err(|_| PngError::ancillary(Self::CHUNK_TYPE, "mDCV chunk parsing"))?;
+        let point = |i: usize| (values[i] as f32 * 0.00002, values[i + 1] as f32 * 0.00002);
+        Ok(Self {
+            red: point(0),
+            green: point(2),
+            blue: point(4),
+            white_point: point(6),
+            max_luminance: max_luminance as f32 * 0.0001,
+            min_luminance: min_luminance as f32 * 0.0001,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = [self.red, self.green, self.blue, self.white_point]
+            .iter()
+            .flat_map(|&(x, y)| [x, y])
+            .flat_map(|v| ((v / 0.00002).round() as u16).to_be_bytes())
+            .collect();
+        for luminance in [self.max_luminance, self.min_luminance] {
+            bytes.extend(((luminance / 0.0001).round() as u32).to_be_bytes());
+        }
+        bytes
+    }
+}
+
+impl fmt::Display for MasteringDisplayColorVolume {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Red: {:?}\nGreen: {:?}\nBlue: {:?}\nWhite point: {:?}\nLuminance: {} to {} cd/m²",
+            self.red,
+            self.green,
+            self.blue,
+            self.white_point,
+            self.min_luminance,
+            self.max_luminance
+        )
+    }
+}
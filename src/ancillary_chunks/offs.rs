@@ -0,0 +1,87 @@
+use std::fmt;
+
+use nom::{
+    number::complete::{be_i32, u8},
+    IResult,
+};
+
+use crate::error::{PngError, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum OffsetUnit {
+    Pixel,
+    Micrometer,
+}
+
+impl OffsetUnit {
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Self::Pixel),
+            1 => Some(Self::Micrometer),
+            _ => None,
+        }
+    }
+
+    fn to_u8(self) -> u8 {
+        match self {
+            Self::Pixel => 0,
+            Self::Micrometer => 1,
+        }
+    }
+}
+
+/// Position of the image on a page or in a larger image
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ImageOffset {
+    pub x: i32,
+    pub y: i32,
+    pub unit: OffsetUnit,
+}
+
+impl ImageOffset {
+    pub const CHUNK_TYPE: &'static str = "oFFs";
+
+    pub fn parse(input: &[u8]) -> Result<Self> {
+        fn parse_nom(input: &[u8]) -> IResult<&[u8], (i32, i32, u8)> {
+            let (input, x) = be_i32(input)?;
+            let (input, y) = be_i32(input)?;
+            let (input, unit) = u8(input)?;
+            Ok((input, (x, y, unit)))
+        }
+
+        if input.len() != 9 {
+            return Err(PngError::ancillary(
+                Self::CHUNK_TYPE,
+                "Invalid oFFs chunk length",
+            ));
+        }
+        let (_, (x, y, unit)) = parse_nom(input)
+            .map_err(|_| PngError::ancillary(Self::CHUNK_TYPE, "oFFs chunk parsing"))?;
+        let unit = OffsetUnit::from_u8(unit).ok_or_else(|| {
+            PngError::ancillary(
+                Self::CHUNK_TYPE,
+                format!("Invalid unit specifier: {}", unit),
+            )
+        })?;
+        Ok(Self { x, y, unit })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.x.to_be_bytes().to_vec();
+        bytes.extend_from_slice(&self.y.to_be_bytes());
+        bytes.push(self.unit.to_u8());
+        bytes
+    }
+}
+
+impl fmt::Display for ImageOffset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unit = match self.unit {
+            OffsetUnit::Pixel => "px",
+            OffsetUnit::Micrometer => "µm",
+        };
+        write!(f, "Offset: {}{} x {}{}", self.x, unit, self.y, unit)
+    }
+}
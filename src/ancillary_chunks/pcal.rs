@@ -0,0 +1,256 @@
+use std::{borrow::Cow, fmt};
+
+use nom::{
+    bytes::complete::take_until,
+    number::complete::{be_i32, u8},
+    IResult,
+};
+
+use crate::error::{PngError, Result};
+
+use super::text::{iso_8859_1_to_string, string_to_iso_8859_1};
+
+/// Parses a floating-point string of the PNG spec: a sign, digits with an optional
+/// decimal point and an optional exponent. Unlike `str::parse`, "inf" and "nan" are rejected.
+pub(crate) fn parse_ascii_float(bytes: &[u8]) -> Option<(&str, f64)> {
+    let s = std::str::from_utf8(bytes).ok()?;
+    let valid = s
+        .bytes()
+        .all(|b| b.is_ascii_digit() || matches!(b, b'+' | b'-' | b'.' | b'e' | b'E'));
+    if !valid {
+        return None;
+    }
+    Some((s, s.parse().ok()?))
+}
+
+/// Maps the linearly scaled sample `X` to the physical value `Y`
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum Equation {
+    /// `Y = p0 + p1 * X / (X1 - X0)`
+    Linear,
+    /// `Y = p0 + p1 * exp(p2 * X / (X1 - X0))`
+    Exponential,
+    /// `Y = p0 + p1 * pow(p2, X / (X1 - X0))`
+    ArbitraryBaseExponential,
+    /// `Y = p0 + p1 * sinh(p2 * (X - p3) / (X1 - X0))`
+    HyperbolicSine,
+}
+
+impl Equation {
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Self::Linear),
+            1 => Some(Self::Exponential),
+            2 => Some(Self::ArbitraryBaseExponential),
+            3 => Some(Self::HyperbolicSine),
+            _ => None,
+        }
+    }
+
+    fn to_u8(self) -> u8 {
+        match self {
+            Self::Linear => 0,
+            Self::Exponential => 1,
+            Self::ArbitraryBaseExponential => 2,
+            Self::HyperbolicSine => 3,
+        }
+    }
+
+    pub fn parameter_count(self) -> usize {
+        match self {
+            Self::Linear => 2,
+            Self::Exponential => 3,
+            Self::ArbitraryBaseExponential | Self::HyperbolicSine => 4,
+        }
+    }
+}
+
+/// Calibration of the sample values to a physical quantity
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PixelCalibration<'a> {
+    pub name: Cow<'a, str>,
+    /// `X` of the zero sample
+    pub x0: i32,
+    /// `X` of the maximum sample
+    pub x1: i32,
+    pub equation: Equation,
+    /// Symbol or description of the unit of the physical values
+    pub unit: Cow<'a, str>,
+    /// The parameters as written in the chunk
+    pub parameters: Vec<&'a str>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    values: Vec<f64>,
+}
+
+impl<'a> PixelCalibration<'a> {
+    pub const CHUNK_TYPE: &'static str = "pCAL";
+
+    pub fn parse(input: &'a [u8]) -> Result<Self> {
+        type PcalRaw<'a> = (&'a [u8], i32, i32, u8, u8, &'a [u8]);
+        fn parse_nom(input: &[u8]) -> IResult<&[u8], PcalRaw<'_>> {
+            let (input, name) = take_until(&[0][..])(input)?;
+            let (input, _) = u8(input)?;
+            let (input, x0) = be_i32(input)?;
+            let (input, x1) = be_i32(input)?;
+            let (input, equation) = u8(input)?;
+            let (input, parameter_count) = u8(input)?;
+            let (input, unit) = take_until(&[0][..])(input)?;
+            let (input, _) = u8(input)?;
+            Ok((input, (name, x0, x1, equation, parameter_count, unit)))
+        }
+
+        let invalid = |reason: String| PngError::ancillary(Self::CHUNK_TYPE, reason);
+        let (parameters, (name, x0, x1, equation, parameter_count, unit)) =
+            parse_nom(input).map_err(|_| invalid("pCAL chunk parsing".to_string()))?;
+        if name.is_empty() || name.len() > 79 {
+            return Err(invalid(
+                "Calibration name must be 1 to 79 bytes long".to_string(),
+            ));
+        }
+        if x0 == x1 {
+            return Err(invalid("X0 and X1 must differ".to_string()));
+        }
+        let equation = Equation::from_u8(equation)
+            .ok_or_else(|| invalid(format!("Invalid equation type: {}", equation)))?;
+        if parameter_count as usize != equation.parameter_count() {
+            return Err(invalid(format!(
+                "{:?} equation takes {} parameters, not {}",
+                equation,
+                equation.parameter_count(),
+                parameter_count
+            )));
+        }
+
+        let (parameters, values) = parameters
+            .split(|&b| b == 0)
+            .map(|parameter| {
+                parse_ascii_float(parameter)
+                    .ok_or_else(|| invalid("Invalid floating-point parameter".to_string()))
+            })
+            .collect::<Result<(Vec<_>, Vec<_>)>>()?;
+        if parameters.len() != equation.parameter_count() {
+            return Err(invalid(format!(
+                "Expected {} parameters, found {}",
+                equation.parameter_count(),
+                parameters.len()
+            )));
+        }
+
+        Ok(Self {
+            name: iso_8859_1_to_string(name),
+            x0,
+            x1,
+            equation,
+            unit: iso_8859_1_to_string(unit),
+            parameters,
+            values,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = string_to_iso_8859_1(&self.name);
+        bytes.push(0);
+        bytes.extend_from_slice(&self.x0.to_be_bytes());
+        bytes.extend_from_slice(&self.x1.to_be_bytes());
+        bytes.push(self.equation.to_u8());
+        bytes.push(self.parameters.len() as u8);
+        bytes.extend(string_to_iso_8859_1(&self.unit));
+        bytes.push(0);
+        bytes.extend_from_slice(self.parameters.join("\0").as_bytes());
+        bytes
+    }
+
+    /// The parameters `p0` to `p3` as numbers
+    pub fn parameter_values(&self) -> &[f64] {
+        &self.values
+    }
+
+    /// The physical value of a raw sample of an image with the given bit depth
+    pub fn physical_value(&self, sample: u16, bit_depth: u8) -> f64 {
+        let max = (1i64 << bit_depth) - 1;
+        let (x0, x1) = (self.x0 as i64, self.x1 as i64);
+        // Integer arithmetic with rounding, as the spec defines it
+        let x = x0 + (sample as i64 * (x1 - x0) + max / 2) / max;
+        let ratio = x as f64 / (x1 - x0) as f64;
+        let p = &self.values;
+        match self.equation {
+            Equation::Linear => p[0] + p[1] * ratio,
+            Equation::Exponential => p[0] + p[1] * (p[2] * ratio).exp(),
+            Equation::ArbitraryBaseExponential => p[0] + p[1] * p[2].powf(ratio),
+            Equation::HyperbolicSine => {
+                p[0] + p[1] * (p[2] * (x as f64 - p[3]) / (x1 - x0) as f64).sinh()
+            }
+        }
+    }
+}
+
+impl fmt::Display for PixelCalibration<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Name: {}\nX0: {}, X1: {}\nEquation: {:?}\nUnit: {}\nParameters: {}",
+            self.name,
+            self.x0,
+            self.x1,
+            self.equation,
+            self.unit,
+            self.parameters.join(", ")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pcal(x0: i32, x1: i32, equation: u8, parameters: &[&str]) -> Vec<u8> {
+        let mut bytes = b"Height\0".to_vec();
+        bytes.extend_from_slice(&x0.to_be_bytes());
+        bytes.extend_from_slice(&x1.to_be_bytes());
+        bytes.push(equation);
+        bytes.push(parameters.len() as u8);
+        bytes.extend_from_slice(b"m\0");
+        bytes.extend_from_slice(parameters.join("\0").as_bytes());
+        bytes
+    }
+
+    #[test]
+    fn samples_are_rounded_to_the_nearest_x() {
+        let data = pcal(0, 1000, 0, &["10", "2"]);
+        let calibration = PixelCalibration::parse(&data).unwrap();
+        assert_eq!(calibration.to_bytes(), data);
+        // 1 * 1000 / 255 = 3.92 and 128 * 1000 / 255 = 501.96 round up
+        assert_eq!(calibration.physical_value(1, 8), 10.008);
+        assert_eq!(calibration.physical_value(128, 8), 11.004);
+        assert_eq!(calibration.physical_value(255, 8), 12.0);
+
+        let data = pcal(-100, 100, 0, &["0", "1"]);
+        let calibration = PixelCalibration::parse(&data).unwrap();
+        assert_eq!(calibration.physical_value(0, 8), -0.5);
+        assert_eq!(calibration.physical_value(65535, 16), 0.5);
+    }
+
+    #[test]
+    fn every_equation_type_is_applied() {
+        let value = |equation, parameters: &[&str], sample| {
+            let data = pcal(0, 1000, equation, parameters);
+            PixelCalibration::parse(&data)
+                .unwrap()
+                .physical_value(sample, 8)
+        };
+        assert_eq!(value(1, &["1", "2", "1"], 255), 1.0 + 2.0 * 1f64.exp());
+        assert_eq!(value(2, &["0", "3", "2", "0"], 255), 6.0);
+        assert_eq!(value(3, &["0", "1", "1", "0"], 128), 0.502f64.sinh());
+        assert_eq!(value(3, &["0", "1", "1", "1000"], 255), 0.0);
+    }
+
+    #[test]
+    fn invalid_calibrations_are_rejected() {
+        assert!(PixelCalibration::parse(&pcal(5, 5, 0, &["0", "1"])).is_err());
+        assert!(PixelCalibration::parse(&pcal(0, 1, 4, &["0", "1"])).is_err());
+        assert!(PixelCalibration::parse(&pcal(0, 1, 1, &["0", "1"])).is_err());
+        assert!(PixelCalibration::parse(&pcal(0, 1, 0, &["0", "inf"])).is_err());
+    }
+}
@@ -1,12 +1,25 @@
 use std::fmt;
 
-use anyhow::Context;
 use nom::{
     number::complete::{be_u32, be_u8},
     IResult,
 };
 
-#[derive(Debug, PartialEq)]
+use crate::error::{PngError, Result};
+
+const METERS_PER_INCH: f32 = 0.0254;
+
+/// Converts a resolution in dots per inch to pixels per meter, the unit of pHYs
+pub fn dpi_to_pixels_per_meter(dpi: f32) -> u32 {
+    (dpi / METERS_PER_INCH).round() as u32
+}
+
+pub fn pixels_per_meter_to_dpi(pixels_per_meter: u32) -> f32 {
+    pixels_per_meter as f32 * METERS_PER_INCH
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum UnitSpecifier {
     Unknown,
     Meter,
@@ -20,13 +33,21 @@ impl UnitSpecifier {
             _ => None,
         }
     }
+    fn to_u8(self) -> u8 {
+        match self {
+            UnitSpecifier::Unknown => 0,
+            UnitSpecifier::Meter => 1,
+        }
+    }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct PhysicalUnits {
     pub pixels_per_unit_x: u32,
     pub pixels_per_unit_y: u32,
     pub unit_specifier: UnitSpecifier,
+    /// Width of the image in units, meters unless the unit is unknown
     pub actual_width: f32,
     pub actual_height: f32,
 }
@@ -34,7 +55,7 @@ pub struct PhysicalUnits {
 impl PhysicalUnits {
     pub const CHUNK_TYPE: &'static str = "pHYs";
 
-    pub fn parse(input: &[u8], width: u32, height: u32) -> anyhow::Result<PhysicalUnits> {
+    pub fn parse(input: &[u8], width: u32, height: u32) -> Result<PhysicalUnits> {
         fn parse_nom(input: &[u8]) -> IResult<&[u8], (u32, u32, u8)> {
             let (input, pixels_per_unit_x) = be_u32(input)?;
             let (input, pixels_per_unit_y) = be_u32(input)?;
@@ -46,11 +67,11 @@ impl PhysicalUnits {
         }
 
         let (_, (pixels_per_unit_x, pixels_per_unit_y, unit_specifier_byte)) = parse_nom(input)
-            .map_err(|err| err.to_owned())
-            .context("pHYs chunk parsing")?;
+            .map_err(|_| PngError::ancillary(Self::CHUNK_TYPE, "pHYs chunk parsing"))?;
 
-        let unit_specifier = UnitSpecifier::from_u8(unit_specifier_byte)
-            .context("Unit specifier in phys chunk invalid")?;
+        let unit_specifier = UnitSpecifier::from_u8(unit_specifier_byte).ok_or_else(|| {
+            PngError::ancillary(Self::CHUNK_TYPE, "Unit specifier in phys chunk invalid")
+        })?;
 
         let actual_width = width as f32 / pixels_per_unit_x as f32;
         let actual_height = height as f32 / pixels_per_unit_y as f32;
@@ -63,6 +84,47 @@ impl PhysicalUnits {
             actual_height,
         })
     }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.pixels_per_unit_x.to_be_bytes().to_vec();
+        bytes.extend_from_slice(&self.pixels_per_unit_y.to_be_bytes());
+        bytes.push(self.unit_specifier.to_u8());
+        bytes
+    }
+
+    /// Width of a pixel divided by its height, known even when the unit isn't.
+    /// `None` when either axis has no pixels per unit.
+    pub fn pixel_aspect_ratio(&self) -> Option<f32> {
+        (self.pixels_per_unit_x != 0 && self.pixels_per_unit_y != 0)
+            .then(|| self.pixels_per_unit_y as f32 / self.pixels_per_unit_x as f32)
+    }
+
+    /// Horizontal and vertical resolution, `None` unless the unit is the meter
+    pub fn dpi(&self) -> Option<(f32, f32)> {
+        (self.unit_specifier == UnitSpecifier::Meter).then(|| {
+            (
+                pixels_per_meter_to_dpi(self.pixels_per_unit_x),
+                pixels_per_meter_to_dpi(self.pixels_per_unit_y),
+            )
+        })
+    }
+
+    /// Printed width and height in centimeters, `None` unless the unit is the meter
+    pub fn size_cm(&self) -> Option<(f32, f32)> {
+        self.size_meters()
+            .map(|(width, height)| (width * 100.0, height * 100.0))
+    }
+
+    /// Printed width and height in inches, `None` unless the unit is the meter
+    pub fn size_inches(&self) -> Option<(f32, f32)> {
+        self.size_meters()
+            .map(|(width, height)| (width / METERS_PER_INCH, height / METERS_PER_INCH))
+    }
+
+    fn size_meters(&self) -> Option<(f32, f32)> {
+        (self.unit_specifier == UnitSpecifier::Meter && self.pixel_aspect_ratio().is_some())
+            .then_some((self.actual_width, self.actual_height))
+    }
 }
 impl fmt::Display for PhysicalUnits {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -74,6 +136,72 @@ impl fmt::Display for PhysicalUnits {
             f,
             "Physical Ratio: {:.2}{} x {:.2}{}",
             self.actual_width, unit, self.actual_height, unit
-        )
+        )?;
+        if let (Some((dpi_x, dpi_y)), Some((cm_x, cm_y)), Some((in_x, in_y))) =
+            (self.dpi(), self.size_cm(), self.size_inches())
+        {
+            write!(
+                f,
+                "\nResolution: {:.0} x {:.0} DPI\nSize: {:.2}cm x {:.2}cm, {:.2}in x {:.2}in",
+                dpi_x, dpi_y, cm_x, cm_y, in_x, in_y
+            )?;
+        }
+        if let Some(ratio) = self.pixel_aspect_ratio().filter(|&ratio| ratio != 1.0) {
+            write!(f, "\nPixel aspect ratio: {:.3}", ratio)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn phys(x: u32, y: u32, unit: u8) -> Vec<u8> {
+        let mut bytes = x.to_be_bytes().to_vec();
+        bytes.extend_from_slice(&y.to_be_bytes());
+        bytes.push(unit);
+        bytes
+    }
+
+    fn assert_close((a, b): (f32, f32), (expected_a, expected_b): (f32, f32)) {
+        assert!(
+            (a - expected_a).abs() < 1e-3 && (b - expected_b).abs() < 1e-3,
+            "{:?} != {:?}",
+            (a, b),
+            (expected_a, expected_b)
+        );
+    }
+
+    #[test]
+    fn dpi_round_trips_through_pixels_per_meter() {
+        assert_eq!(dpi_to_pixels_per_meter(300.0), 11811);
+        assert_eq!(dpi_to_pixels_per_meter(72.0), 2835);
+        assert_eq!(pixels_per_meter_to_dpi(11811).round(), 300.0);
+    }
+
+    #[test]
+    fn meters_give_resolution_and_printed_size() {
+        let data = phys(11811, 11811, 1);
+        let units = PhysicalUnits::parse(&data, 600, 300).unwrap();
+        assert_eq!(units.to_bytes(), data);
+        assert_close(units.dpi().unwrap(), (300.0, 300.0));
+        assert_close(units.size_inches().unwrap(), (2.0, 1.0));
+        assert_close(units.size_cm().unwrap(), (5.08, 2.54));
+        assert_eq!(units.pixel_aspect_ratio(), Some(1.0));
+    }
+
+    #[test]
+    fn unknown_units_only_give_the_aspect_ratio() {
+        // Twice as many pixels per unit horizontally, so pixels are half as wide as high
+        let units = PhysicalUnits::parse(&phys(2, 1, 0), 10, 10).unwrap();
+        assert_eq!(units.pixel_aspect_ratio(), Some(0.5));
+        assert_eq!(units.dpi(), None);
+        assert_eq!(units.size_cm(), None);
+
+        let units = PhysicalUnits::parse(&phys(0, 1, 1), 10, 10).unwrap();
+        assert_eq!(units.pixel_aspect_ratio(), None);
+        assert_eq!(units.size_inches(), None);
+        assert!(PhysicalUnits::parse(&phys(1, 1, 2), 10, 10).is_err());
     }
 }
@@ -0,0 +1,181 @@
+use std::fmt;
+
+use crate::color_type::ColorType;
+use crate::error::{PngError, Result};
+use crate::png_parser::Pixel16;
+
+/// Number of significant bits of the original samples, per channel
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum SignificantBits {
+    Grayscale {
+        gray: u8,
+    },
+    /// Truecolor and indexed images, for the latter the bits refer to the palette entries
+    Rgb {
+        red: u8,
+        green: u8,
+        blue: u8,
+    },
+    GrayscaleAlpha {
+        gray: u8,
+        alpha: u8,
+    },
+    Rgba {
+        red: u8,
+        green: u8,
+        blue: u8,
+        alpha: u8,
+    },
+}
+
+impl SignificantBits {
+    pub const CHUNK_TYPE: &'static str = "sBIT";
+
+    /// `sample_depth` is the bit depth, or 8 for indexed images
+    pub fn parse(content: &[u8], color_type: &ColorType, sample_depth: u8) -> Result<Self> {
+        let invalid = || PngError::ancillary(Self::CHUNK_TYPE, "Invalid sBIT chunk content");
+        if content.iter().any(|&bits| bits == 0 || bits > sample_depth) {
+            return Err(PngError::ancillary(
+                Self::CHUNK_TYPE,
+                format!("Significant bits must be between 1 and {}", sample_depth),
+            ));
+        }
+        Ok(match (color_type, content) {
+            (ColorType::Grayscale { .. }, &[gray]) => Self::Grayscale { gray },
+            (ColorType::Rgb { .. } | ColorType::Palette(_), &[red, green, blue]) => {
+                Self::Rgb { red, green, blue }
+            }
+            (ColorType::GrayscaleAlpha, &[gray, alpha]) => Self::GrayscaleAlpha { gray, alpha },
+            (ColorType::Rgba, &[red, green, blue, alpha]) => Self::Rgba {
+                red,
+                green,
+                blue,
+                alpha,
+            },
+            _ => return Err(invalid()),
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match *self {
+            Self::Grayscale { gray } => vec![gray],
+            Self::Rgb { red, green, blue } => vec![red, green, blue],
+            Self::GrayscaleAlpha { gray, alpha } => vec![gray, alpha],
+            Self::Rgba {
+                red,
+                green,
+                blue,
+                alpha,
+            } => vec![red, green, blue, alpha],
+        }
+    }
+
+    /// The significant bits of the same samples stored as `color_type` with `sample_depth`
+    /// bits per sample. Gray takes the most significant bits of the color channels.
+    pub fn convert(&self, color_type: &ColorType, sample_depth: u8) -> Self {
+        let [red, green, blue, alpha] = self
+            .channels(sample_depth)
+            .map(|bits| bits.min(sample_depth));
+        let gray = red.max(green).max(blue);
+        match color_type {
+            ColorType::Grayscale { .. } => Self::Grayscale { gray },
+            ColorType::Rgb { .. } | ColorType::Palette(_) => Self::Rgb { red, green, blue },
+            ColorType::GrayscaleAlpha => Self::GrayscaleAlpha { gray, alpha },
+            ColorType::Rgba => Self::Rgba {
+                red,
+                green,
+                blue,
+                alpha,
+            },
+        }
+    }
+
+    /// Significant bits of the red, green, blue and alpha channels of decoded pixels,
+    /// channels the image doesn't store use the full `sample_depth`
+    pub fn channels(&self, sample_depth: u8) -> [u8; 4] {
+        match *self {
+            Self::Grayscale { gray } => [gray, gray, gray, sample_depth],
+            Self::Rgb { red, green, blue } => [red, green, blue, sample_depth],
+            Self::GrayscaleAlpha { gray, alpha } => [gray, gray, gray, alpha],
+            Self::Rgba {
+                red,
+                green,
+                blue,
+                alpha,
+            } => [red, green, blue, alpha],
+        }
+    }
+
+    /// Rescales a pixel decoded from samples of `sample_depth` bits so that the
+    /// significant bits alone span the full 16 bit range
+    pub fn rescale(&self, pixel: Pixel16, sample_depth: u8) -> Pixel16 {
+        let [r, g, b, a] = self.channels(sample_depth);
+        let max = (1u32 << sample_depth) - 1;
+        let rescale = |value: u16, bits: u8| {
+            // Back to the original sample, then keep its significant bits
+            let sample = (value as u32 * max + u16::MAX as u32 / 2) / u16::MAX as u32;
+            let significant = sample >> (sample_depth - bits);
+            (significant * u16::MAX as u32 / ((1 << bits) - 1)) as u16
+        };
+        let (pr, pg, pb, pa) = pixel;
+        (
+            rescale(pr, r),
+            rescale(pg, g),
+            rescale(pb, b),
+            rescale(pa, a),
+        )
+    }
+}
+
+impl fmt::Display for SignificantBits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Grayscale { gray } => write!(f, "Significant bits: gray {}", gray),
+            Self::Rgb { red, green, blue } => write!(
+                f,
+                "Significant bits: red {}, green {}, blue {}",
+                red, green, blue
+            ),
+            Self::GrayscaleAlpha { gray, alpha } => {
+                write!(f, "Significant bits: gray {}, alpha {}", gray, alpha)
+            }
+            Self::Rgba {
+                red,
+                green,
+                blue,
+                alpha,
+            } => write!(
+                f,
+                "Significant bits: red {}, green {}, blue {}, alpha {}",
+                red, green, blue, alpha
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn five_bit_samples_span_the_full_range() {
+        let sbit =
+            SignificantBits::parse(&[5, 5, 5], &ColorType::Rgb { transparent: None }, 8).unwrap();
+        // 5 bit samples shifted into 8 bits, then widened to 16 bits by the decoder
+        let widen = |sample: u16| (sample << 3) * 257;
+        let pixel = (widen(31), widen(16), widen(0), u16::MAX);
+        assert_eq!(
+            sbit.rescale(pixel, 8),
+            (u16::MAX, (16 * 65535 / 31) as u16, 0, u16::MAX)
+        );
+    }
+
+    #[test]
+    fn bits_over_the_sample_depth_are_rejected() {
+        let gray = ColorType::Grayscale { transparent: None };
+        assert!(SignificantBits::parse(&[9], &gray, 8).is_err());
+        assert!(SignificantBits::parse(&[0], &gray, 8).is_err());
+        assert!(SignificantBits::parse(&[4, 4], &gray, 8).is_err());
+    }
+}
@@ -0,0 +1,108 @@
+use std::fmt;
+
+use crate::error::{PngError, Result};
+
+use super::pcal::parse_ascii_float;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum ScaleUnit {
+    Meter,
+    Radian,
+}
+
+impl ScaleUnit {
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            1 => Some(Self::Meter),
+            2 => Some(Self::Radian),
+            _ => None,
+        }
+    }
+
+    fn to_u8(self) -> u8 {
+        match self {
+            Self::Meter => 1,
+            Self::Radian => 2,
+        }
+    }
+}
+
+/// Physical size of the area covered by a pixel
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PhysicalScale {
+    pub unit: ScaleUnit,
+    pub pixel_width: f64,
+    pub pixel_height: f64,
+    /// The width and height as written in the chunk
+    pub width_text: String,
+    pub height_text: String,
+}
+
+impl PhysicalScale {
+    pub const CHUNK_TYPE: &'static str = "sCAL";
+
+    pub fn parse(input: &[u8]) -> Result<Self> {
+        let invalid = |reason: &str| PngError::ancillary(Self::CHUNK_TYPE, reason);
+        let (&unit, rest) = input
+            .split_first()
+            .ok_or_else(|| invalid("Empty sCAL chunk"))?;
+        let unit = ScaleUnit::from_u8(unit).ok_or_else(|| invalid("Invalid unit specifier"))?;
+
+        let mut values = rest.split(|&b| b == 0).map(parse_ascii_float);
+        let (Some(Some(width)), Some(Some(height)), None) =
+            (values.next(), values.next(), values.next())
+        else {
+            return Err(invalid("sCAL must hold a width and a height"));
+        };
+        if width.1 <= 0.0 || height.1 <= 0.0 {
+            return Err(invalid("sCAL width and height must be positive"));
+        }
+
+        Ok(Self {
+            unit,
+            pixel_width: width.1,
+            pixel_height: height.1,
+            width_text: width.0.to_string(),
+            height_text: height.0.to_string(),
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![self.unit.to_u8()];
+        bytes.extend_from_slice(self.width_text.as_bytes());
+        bytes.push(0);
+        bytes.extend_from_slice(self.height_text.as_bytes());
+        bytes
+    }
+}
+
+impl fmt::Display for PhysicalScale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unit = match self.unit {
+            ScaleUnit::Meter => "m",
+            ScaleUnit::Radian => "rad",
+        };
+        write!(
+            f,
+            "Pixel size: {}{} x {}{}",
+            self.width_text, unit, self.height_text, unit
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scale_keeps_the_written_text() {
+        let scale = PhysicalScale::parse(b"\x011.5e-3\x000.002").unwrap();
+        assert_eq!(scale.unit, ScaleUnit::Meter);
+        assert_eq!((scale.pixel_width, scale.pixel_height), (0.0015, 0.002));
+        assert_eq!(scale.to_bytes(), b"\x011.5e-3\x000.002");
+        assert!(PhysicalScale::parse(b"\x011\x00-1").is_err());
+        assert!(PhysicalScale::parse(b"\x011").is_err());
+    }
+}
@@ -0,0 +1,158 @@
+use std::{borrow::Cow, fmt};
+
+use nom::{
+    bytes::complete::take_until,
+    number::complete::{be_u16, u8},
+    IResult,
+};
+
+use crate::error::{PngError, Result};
+use crate::plte::Palette;
+
+use super::text::{iso_8859_1_to_string, string_to_iso_8859_1};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SuggestedPaletteEntry {
+    pub red: u16,
+    pub green: u16,
+    pub blue: u16,
+    pub alpha: u16,
+    /// Relative usage of the entry, 0 if unknown
+    pub frequency: u16,
+}
+
+/// A palette suggested for displays that can't show every color of the image
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SuggestedPalette<'a> {
+    pub name: Cow<'a, str>,
+    /// 8 or 16, the depth of the entry samples
+    pub sample_depth: u8,
+    pub entries: Vec<SuggestedPaletteEntry>,
+}
+
+impl<'a> SuggestedPalette<'a> {
+    pub const CHUNK_TYPE: &'static str = "sPLT";
+
+    pub fn parse(input: &'a [u8]) -> Result<Self> {
+        fn parse_nom(input: &[u8]) -> IResult<&[u8], (&[u8], u8)> {
+            let (input, name) = take_until(&[0][..])(input)?;
+            let (input, _) = u8(input)?;
+            let (input, sample_depth) = u8(input)?;
+            Ok((input, (name, sample_depth)))
+        }
+
+        let (mut input, (name, sample_depth)) = parse_nom(input)
+            .map_err(|_| PngError::ancillary(Self::CHUNK_TYPE, "sPLT chunk parsing"))?;
+        if name.is_empty() || name.len() > 79 {
+            return Err(PngError::ancillary(
+                Self::CHUNK_TYPE,
+                "Palette name must be 1 to 79 bytes long",
+            ));
+        }
+        let entry_len = match sample_depth {
+            8 => 6,
+            16 => 10,
+            _ => {
+                return Err(PngError::ancillary(
+                    Self::CHUNK_TYPE,
+                    format!("Invalid sample depth: {}", sample_depth),
+                ))
+            }
+        };
+        if !input.len().is_multiple_of(entry_len) {
+            return Err(PngError::ancillary(
+                Self::CHUNK_TYPE,
+                "sPLT entries don't fill the chunk",
+            ));
+        }
+
+        let mut entries = Vec::with_capacity(input.len() / entry_len);
+        while !input.is_empty() {
+            let sample = |input| -> IResult<&[u8], u16> {
+                match sample_depth {
+                    8 => u8(input).map(|(input, v)| (input, v as u16)),
+                    _ => be_u16(input),
+                }
+            };
+            let parse_entry = |input| -> IResult<&[u8], SuggestedPaletteEntry> {
+                let (input, red) = sample(input)?;
+                let (input, green) = sample(input)?;
+                let (input, blue) = sample(input)?;
+                let (input, alpha) = sample(input)?;
+                let (input, frequency) = be_u16(input)?;
+                Ok((
+                    input,
+                    SuggestedPaletteEntry {
+                        red,
+                        green,
+                        blue,
+                        alpha,
+                        frequency,
+                    },
+                ))
+            };
+            let (rest, entry) = parse_entry(input)
+                .map_err(|_| PngError::ancillary(Self::CHUNK_TYPE, "sPLT entry parsing"))?;
+            entries.push(entry);
+            input = rest;
+        }
+
+        Ok(Self {
+            name: iso_8859_1_to_string(name),
+            sample_depth,
+            entries,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = string_to_iso_8859_1(&self.name);
+        bytes.push(0);
+        bytes.push(self.sample_depth);
+        for entry in &self.entries {
+            for sample in [entry.red, entry.green, entry.blue, entry.alpha] {
+                match self.sample_depth {
+                    8 => bytes.push(sample as u8),
+                    _ => bytes.extend(sample.to_be_bytes()),
+                }
+            }
+            bytes.extend(entry.frequency.to_be_bytes());
+        }
+        bytes
+    }
+
+    /// The entries as an 8 bit palette, e.g. to quantize an image with [`Palette::nearest`]
+    pub fn to_palette(&self) -> Palette {
+        let to_8bit = |v: u16| match self.sample_depth {
+            8 => v as u8,
+            _ => (v >> 8) as u8,
+        };
+        Palette {
+            entries: self
+                .entries
+                .iter()
+                .map(|e| {
+                    (
+                        to_8bit(e.red),
+                        to_8bit(e.green),
+                        to_8bit(e.blue),
+                        to_8bit(e.alpha),
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Display for SuggestedPalette<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Name: {}\nSample depth: {}\nEntries: {}",
+            self.name,
+            self.sample_depth,
+            self.entries.len()
+        )
+    }
+}
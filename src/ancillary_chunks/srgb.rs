@@ -0,0 +1,84 @@
+use std::fmt;
+
+use crate::error::{PngError, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum RenderingIntent {
+    Perceptual,
+    RelativeColorimetric,
+    Saturation,
+    AbsoluteColorimetric,
+}
+
+impl RenderingIntent {
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Self::Perceptual),
+            1 => Some(Self::RelativeColorimetric),
+            2 => Some(Self::Saturation),
+            3 => Some(Self::AbsoluteColorimetric),
+            _ => None,
+        }
+    }
+
+    fn to_u8(self) -> u8 {
+        match self {
+            Self::Perceptual => 0,
+            Self::RelativeColorimetric => 1,
+            Self::Saturation => 2,
+            Self::AbsoluteColorimetric => 3,
+        }
+    }
+}
+
+/// The image samples are in the sRGB color space
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Srgb {
+    pub rendering_intent: RenderingIntent,
+}
+
+impl Srgb {
+    pub const CHUNK_TYPE: &'static str = "sRGB";
+
+    pub fn parse(content: &[u8]) -> Result<Self> {
+        let [intent] = content else {
+            return Err(PngError::ancillary(
+                Self::CHUNK_TYPE,
+                "Invalid sRGB chunk content",
+            ));
+        };
+        let rendering_intent = RenderingIntent::from_u8(*intent).ok_or_else(|| {
+            PngError::ancillary(
+                Self::CHUNK_TYPE,
+                format!("Invalid rendering intent: {}", intent),
+            )
+        })?;
+        Ok(Self { rendering_intent })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        vec![self.rendering_intent.to_u8()]
+    }
+}
+
+impl fmt::Display for Srgb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Rendering intent: {:?}", self.rendering_intent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rendering_intent_round_trips() {
+        for intent in 0..4 {
+            assert_eq!(Srgb::parse(&[intent]).unwrap().to_bytes(), [intent]);
+        }
+        assert!(Srgb::parse(&[4]).is_err());
+        assert!(Srgb::parse(&[0, 0]).is_err());
+    }
+}
@@ -0,0 +1,141 @@
+use std::fmt;
+
+use crate::error::{PngError, Result};
+use crate::image_buffer::ImageBuffer;
+
+/// How the two views of a stereo pair are laid out
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum StereoMode {
+    /// The right-eye view is on the left, for viewing with crossed eyes
+    CrossFuse,
+    /// The left-eye view is on the left, for parallel viewing
+    DivergingFuse,
+}
+
+impl StereoMode {
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Self::CrossFuse),
+            1 => Some(Self::DivergingFuse),
+            _ => None,
+        }
+    }
+
+    fn to_u8(self) -> u8 {
+        match self {
+            Self::CrossFuse => 0,
+            Self::DivergingFuse => 1,
+        }
+    }
+}
+
+/// The image is a stereo pair, two subimages side by side
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Stereo {
+    pub mode: StereoMode,
+    /// Width of each subimage
+    pub subimage_width: u32,
+    /// Columns between the subimages, the right one starts on a multiple of 8
+    pub padding: u32,
+}
+
+impl Stereo {
+    pub const CHUNK_TYPE: &'static str = "sTER";
+
+    pub fn parse(content: &[u8], width: u32) -> Result<Self> {
+        let &[mode] = content else {
+            return Err(PngError::ancillary(
+                Self::CHUNK_TYPE,
+                "Invalid sTER chunk content",
+            ));
+        };
+        let mode = StereoMode::from_u8(mode).ok_or_else(|| {
+            PngError::ancillary(Self::CHUNK_TYPE, format!("Invalid stereo mode: {}", mode))
+        })?;
+        let padding = 15 - ((width - 1) % 16);
+        if padding > 7 {
+            return Err(PngError::ancillary(
+                Self::CHUNK_TYPE,
+                format!("Image width {} can't hold a stereo pair", width),
+            ));
+        }
+        Ok(Self {
+            mode,
+            subimage_width: (width - padding) / 2,
+            padding,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        vec![self.mode.to_u8()]
+    }
+
+    /// Returns the image laid out for the viewing `mode`, the subimages are swapped if it
+    /// differs from the mode of the image
+    pub fn to_mode<S: Copy + Default>(
+        &self,
+        image: &ImageBuffer<S>,
+        mode: StereoMode,
+    ) -> ImageBuffer<S> {
+        if mode == self.mode {
+            return image.clone();
+        }
+        let mut swapped = ImageBuffer::new(image.width(), image.height());
+        let right_start = self.subimage_width + self.padding;
+        for y in 0..image.height() {
+            for x in 0..self.subimage_width {
+                swapped.set_pixel(x, y, image.pixel(right_start + x, y));
+                swapped.set_pixel(right_start + x, y, image.pixel(x, y));
+            }
+        }
+        swapped
+    }
+}
+
+impl fmt::Display for Stereo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Mode: {:?}\nSubimage width: {}, padding: {}",
+            self.mode, self.subimage_width, self.padding
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn right_subimage_starts_on_a_multiple_of_8() {
+        let stereo = Stereo::parse(&[1], 16).unwrap();
+        assert_eq!((stereo.subimage_width, stereo.padding), (8, 0));
+        let stereo = Stereo::parse(&[0], 13).unwrap();
+        assert_eq!(stereo.mode, StereoMode::CrossFuse);
+        assert_eq!((stereo.subimage_width, stereo.padding), (5, 3));
+        let stereo = Stereo::parse(&[0], 25).unwrap();
+        assert_eq!((stereo.subimage_width, stereo.padding), (9, 7));
+
+        // 11 columns of padding would be needed
+        assert!(Stereo::parse(&[0], 5).is_err());
+        assert!(Stereo::parse(&[2], 16).is_err());
+        assert!(Stereo::parse(&[], 16).is_err());
+    }
+
+    #[test]
+    fn subimages_are_swapped_for_the_other_mode() {
+        let stereo = Stereo::parse(&[0], 10).unwrap();
+        assert_eq!((stereo.subimage_width, stereo.padding), (2, 6));
+        // The red sample of each pixel is its column
+        let samples = (0..10).flat_map(|x| [x, 0, 0, 255]).collect();
+        let image = ImageBuffer::from_samples(10, 1, samples).unwrap();
+
+        assert_eq!(stereo.to_mode(&image, StereoMode::CrossFuse), image);
+        let swapped = stereo.to_mode(&image, StereoMode::DivergingFuse);
+        let columns = (0..10).map(|x| swapped.pixel(x, 0).0).collect::<Vec<_>>();
+        assert_eq!(columns[..2], [8, 9]);
+        assert_eq!(columns[8..], [0, 1]);
+    }
+}
@@ -3,12 +3,14 @@ use std::{
     fmt::{self, Display},
 };
 
-use anyhow::{anyhow, Context};
 use nom::{bytes::complete::take_until, number::complete::u8, IResult};
 
+use crate::error::{PngError, Result};
 use crate::ihdr::CompressionMethod;
+use crate::limits::DecodeLimits;
+use crate::png_parser::inflate_chunk;
 
-fn iso_8859_1_to_string(bytes: &[u8]) -> Cow<str> {
+pub(crate) fn iso_8859_1_to_string(bytes: &[u8]) -> Cow<'_, str> {
     match std::str::from_utf8(bytes) {
         Ok(s) => Cow::Borrowed(s),
         Err(_) => Cow::Owned(bytes.iter().map(|&b| b as char).collect()),
@@ -17,31 +19,49 @@ fn iso_8859_1_to_string(bytes: &[u8]) -> Cow<str> {
 fn iso_8859_1_to_owned_string(bytes: Vec<u8>) -> String {
     bytes.into_iter().map(|s| s as char).collect()
 }
+pub(crate) fn string_to_iso_8859_1(s: &str) -> Vec<u8> {
+    s.chars()
+        .map(|c| u8::try_from(c as u32).unwrap_or(b'?'))
+        .collect()
+}
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct TextChunk<'a> {
     pub keyword: Cow<'a, str>,
     pub text: Cow<'a, str>,
 }
 impl<'a> TextChunk<'a> {
     pub const CHUNK_TYPE: &'static str = "tEXt";
-    pub fn parse(data: &'a [u8]) -> anyhow::Result<Self> {
+    pub fn parse(data: &'a [u8], limits: &DecodeLimits) -> Result<Self> {
         let mut s = data
             .split(|a| *a == 0)
             .map(|s| iso_8859_1_to_string(s))
             .collect::<Vec<_>>();
 
         if s.len() != 2 {
-            anyhow::bail!("Invalid tEXt multiple null bytes");
+            return Err(PngError::ancillary(
+                Self::CHUNK_TYPE,
+                "Invalid tEXt multiple null bytes",
+            ));
         }
+        limits.check_text_len(s[1].len())?;
         Ok(TextChunk {
             keyword: s.remove(0),
             text: s.remove(0),
         })
     }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = string_to_iso_8859_1(&self.keyword);
+        bytes.push(0);
+        bytes.extend(string_to_iso_8859_1(&self.text));
+        bytes
+    }
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct CompressedTextChunk<'a> {
     pub keyword: Cow<'a, str>,
     pub text: String,
@@ -49,8 +69,8 @@ pub struct CompressedTextChunk<'a> {
 impl<'a> CompressedTextChunk<'a> {
     pub const CHUNK_TYPE: &'static str = "zTXt";
 
-    pub fn parse(input: &'a [u8]) -> anyhow::Result<CompressedTextChunk> {
-        fn parse_nom(input: &[u8]) -> IResult<&[u8], (Cow<str>, u8)> {
+    pub fn parse(input: &'a [u8], limits: &DecodeLimits) -> Result<CompressedTextChunk<'a>> {
+        fn parse_nom(input: &[u8]) -> IResult<&[u8], (Cow<'_, str>, u8)> {
             let (input, keyword) = take_until(&[0][..])(input)?;
             let keyword = iso_8859_1_to_string(keyword);
             let (input, _) = u8(input)?;
@@ -59,22 +79,36 @@ impl<'a> CompressedTextChunk<'a> {
         }
 
         let (compressed, (keyword, compression_method)) = parse_nom(input)
-            .map_err(|e| e.to_owned())
-            .context("zTXt chunk parsing")?;
+            .map_err(|_| PngError::ancillary(Self::CHUNK_TYPE, "zTXt chunk parsing"))?;
 
-        let CompressionMethod::Zlib = CompressionMethod::from_u8(compression_method)
-            .context("Compression method value zTXt")?;
+        let CompressionMethod::Zlib =
+            CompressionMethod::from_u8(compression_method).ok_or_else(|| {
+                PngError::ancillary(Self::CHUNK_TYPE, "Compression method value zTXt")
+            })?;
 
-        let text = iso_8859_1_to_owned_string(
-            inflate::inflate_bytes_zlib(compressed)
-                .map_err(|e| anyhow!("Failed decompression the zTXt chunk: {:?}", e))?,
-        );
+        let text = iso_8859_1_to_owned_string(inflate_chunk(
+            compressed,
+            Self::CHUNK_TYPE,
+            "text length",
+            limits.max_text_len,
+        )?);
 
         Ok(CompressedTextChunk { text, keyword })
     }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = string_to_iso_8859_1(&self.keyword);
+        bytes.push(0);
+        bytes.push(CompressionMethod::Zlib.to_u8());
+        bytes.extend(deflate::deflate_bytes_zlib(&string_to_iso_8859_1(
+            &self.text,
+        )));
+        bytes
+    }
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct InternationalTextChunk<'a> {
     pub keyword: &'a str,
     pub language_tag: &'a str,
@@ -98,9 +132,9 @@ impl CompressionFlags {
 
 impl<'a> InternationalTextChunk<'a> {
     pub const CHUNK_TYPE: &'static str = "iTXt";
-    pub fn parse(input: &'a [u8]) -> anyhow::Result<Self> {
+    pub fn parse(input: &'a [u8], limits: &DecodeLimits) -> Result<Self> {
         type ITXTRaw<'a> = (&'a [u8], u8, u8, &'a [u8], &'a [u8]);
-        fn parse_nom(input: &[u8]) -> IResult<&[u8], ITXTRaw> {
+        fn parse_nom(input: &[u8]) -> IResult<&[u8], ITXTRaw<'_>> {
             let (input, keyword) = take_until(&[0][..])(input)?;
             let (input, _) = u8(input)?;
 
@@ -125,25 +159,35 @@ impl<'a> InternationalTextChunk<'a> {
         }
 
         let (input, (keyword, compression_flags, compression_method, language_tag, translated)) =
-            parse_nom(input)
-                .map_err(|e| e.to_owned())
-                .context("iTXt parsing")?;
+            parse_nom(input).map_err(|_| PngError::ancillary(Self::CHUNK_TYPE, "iTXt parsing"))?;
 
-        let CompressionMethod::Zlib =
-            CompressionMethod::from_u8(compression_method).context("CompressionMethod from_u8")?;
+        let CompressionMethod::Zlib = CompressionMethod::from_u8(compression_method)
+            .ok_or_else(|| PngError::ancillary(Self::CHUNK_TYPE, "CompressionMethod from_u8"))?;
 
-        let compression_flag =
-            CompressionFlags::from_u8(compression_flags).context("CompressionFlags from_u8")?;
-        let keyword = std::str::from_utf8(keyword)?;
-        let language_tag = std::str::from_utf8(language_tag)?;
-        let translated_keyword = std::str::from_utf8(translated)?;
+        let compression_flag = CompressionFlags::from_u8(compression_flags)
+            .ok_or_else(|| PngError::ancillary(Self::CHUNK_TYPE, "CompressionFlags from_u8"))?;
+        let utf8 = |bytes| {
+            std::str::from_utf8(bytes)
+                .map_err(|e| PngError::ancillary(Self::CHUNK_TYPE, e.to_string()))
+        };
+        let keyword = utf8(keyword)?;
+        let language_tag = utf8(language_tag)?;
+        let translated_keyword = utf8(translated)?;
 
         let text = match compression_flag {
-            CompressionFlags::NoCompression => Cow::Borrowed(std::str::from_utf8(input)?),
-            CompressionFlags::Compression => Cow::Owned(String::from_utf8(
-                inflate::inflate_bytes_zlib(input)
-                    .map_err(|e| anyhow!("Failed decompressing iTXt: {:?}", e))?,
-            )?),
+            CompressionFlags::NoCompression => {
+                limits.check_text_len(input.len())?;
+                Cow::Borrowed(utf8(input)?)
+            }
+            CompressionFlags::Compression => Cow::Owned(
+                String::from_utf8(inflate_chunk(
+                    input,
+                    Self::CHUNK_TYPE,
+                    "text length",
+                    limits.max_text_len,
+                )?)
+                .map_err(|e| PngError::ancillary(Self::CHUNK_TYPE, e.to_string()))?,
+            ),
         };
 
         Ok(InternationalTextChunk {
@@ -153,6 +197,20 @@ impl<'a> InternationalTextChunk<'a> {
             text,
         })
     }
+
+    /// The text is always written uncompressed
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.keyword.as_bytes().to_vec();
+        bytes.push(0);
+        bytes.push(0); // compression flag
+        bytes.push(CompressionMethod::Zlib.to_u8());
+        bytes.extend_from_slice(self.language_tag.as_bytes());
+        bytes.push(0);
+        bytes.extend_from_slice(self.translated_keyword.as_bytes());
+        bytes.push(0);
+        bytes.extend_from_slice(self.text.as_bytes());
+        bytes
+    }
 }
 
 impl<'a> Display for TextChunk<'a> {
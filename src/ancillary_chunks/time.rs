@@ -1,11 +1,13 @@
 use std::fmt;
 
-use anyhow::Context;
 use nom::number::complete::{u16, u8};
 use nom::number::Endianness;
 use nom::IResult;
 
+use crate::error::{PngError, Result};
+
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Time {
     pub year: u16,
     pub month: u8,
@@ -24,23 +26,29 @@ impl fmt::Display for Time {
         )
     }
 }
-fn assert_range<T>(value: T, top_limit: T, bottom: T) -> anyhow::Result<()>
+fn assert_range<T>(value: T, top_limit: T, bottom: T) -> Result<()>
 where
     T: PartialOrd + fmt::Display,
 {
     if value > top_limit {
-        anyhow::bail!("Invalid time value: {} > {}", value, top_limit);
+        return Err(PngError::ancillary(
+            Time::CHUNK_TYPE,
+            format!("Invalid time value: {} > {}", value, top_limit),
+        ));
     }
 
     if value < bottom {
-        anyhow::bail!("Invalid time value: {} < {}", value, bottom);
+        return Err(PngError::ancillary(
+            Time::CHUNK_TYPE,
+            format!("Invalid time value: {} < {}", value, bottom),
+        ));
     }
     Ok(())
 }
 impl Time {
     pub const CHUNK_TYPE: &'static str = "tIME";
 
-    pub fn parse(input: &[u8]) -> anyhow::Result<Self> {
+    pub fn parse(input: &[u8]) -> Result<Self> {
         fn parse_nom(input: &[u8]) -> IResult<&[u8], Time> {
             let (input, year) = u16(Endianness::Big)(input)?;
             let (input, month) = u8(input)?;
@@ -61,9 +69,8 @@ impl Time {
             ))
         }
 
-        let (_, time) = parse_nom(input)
-            .map_err(|e| e.to_owned())
-            .context("Time parsing")?;
+        let (_, time) =
+            parse_nom(input).map_err(|_| PngError::ancillary(Self::CHUNK_TYPE, "Time parsing"))?;
         assert_range(time.month, 12, 1)?;
         assert_range(time.day, 31, 1)?;
         assert_range(time.hour, 23, 0)?;
@@ -72,4 +79,10 @@ impl Time {
 
         Ok(time)
     }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.year.to_be_bytes().to_vec();
+        bytes.extend_from_slice(&[self.month, self.day, self.hour, self.minute, self.second]);
+        bytes
+    }
 }
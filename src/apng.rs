@@ -0,0 +1,545 @@
+use std::fmt;
+use std::time::Duration;
+
+use nom::number::complete::{be_u16, be_u32, u8};
+use nom::IResult;
+
+use crate::chunk::RawChunk;
+use crate::error::{PngError, Result};
+use crate::limits::DecodeLimits;
+use crate::png_parser::{inflate_chunk, Image, Pixel, IDAT};
+
+pub const FDAT: &str = "fdAT";
+
+#[derive(Debug, Clone, Copy)]
+pub struct AnimationControl {
+    pub num_frames: u32,
+    /// 0 means the animation loops forever
+    pub num_plays: u32,
+}
+
+impl AnimationControl {
+    pub const CHUNK_TYPE: &'static str = "acTL";
+
+    pub fn parse(input: &[u8]) -> Result<Self> {
+        fn parse_nom(input: &[u8]) -> IResult<&[u8], (u32, u32)> {
+            let (input, num_frames) = be_u32(input)?;
+            let (input, num_plays) = be_u32(input)?;
+            Ok((input, (num_frames, num_plays)))
+        }
+
+        let (_, (num_frames, num_plays)) =
+            parse_nom(input).map_err(|e| PngError::animation(Self::CHUNK_TYPE, e.to_string()))?;
+
+        if num_frames == 0 {
+            return Err(PngError::animation(
+                Self::CHUNK_TYPE,
+                "acTL num_frames can't be 0",
+            ));
+        }
+
+        Ok(Self {
+            num_frames,
+            num_plays,
+        })
+    }
+}
+
+impl fmt::Display for AnimationControl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let plays = if self.num_plays == 0 {
+            "infinite".to_string()
+        } else {
+            self.num_plays.to_string()
+        };
+        write!(f, "Frames: {}\nPlays: {}", self.num_frames, plays)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DisposeOp {
+    None,
+    Background,
+    Previous,
+}
+
+impl DisposeOp {
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Self::None),
+            1 => Some(Self::Background),
+            2 => Some(Self::Previous),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlendOp {
+    Source,
+    Over,
+}
+
+impl BlendOp {
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Self::Source),
+            1 => Some(Self::Over),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FrameControl {
+    pub sequence_number: u32,
+    pub width: u32,
+    pub height: u32,
+    pub x_offset: u32,
+    pub y_offset: u32,
+    pub delay_num: u16,
+    pub delay_den: u16,
+    pub dispose_op: DisposeOp,
+    pub blend_op: BlendOp,
+}
+
+impl FrameControl {
+    pub const CHUNK_TYPE: &'static str = "fcTL";
+
+    pub fn parse(input: &[u8], canvas_width: u32, canvas_height: u32) -> Result<Self> {
+        type FctlRaw = (u32, u32, u32, u32, u32, u16, u16, u8, u8);
+        fn parse_nom(input: &[u8]) -> IResult<&[u8], FctlRaw> {
+            let (input, sequence_number) = be_u32(input)?;
+            let (input, width) = be_u32(input)?;
+            let (input, height) = be_u32(input)?;
+            let (input, x_offset) = be_u32(input)?;
+            let (input, y_offset) = be_u32(input)?;
+            let (input, delay_num) = be_u16(input)?;
+            let (input, delay_den) = be_u16(input)?;
+            let (input, dispose_op) = u8(input)?;
+            let (input, blend_op) = u8(input)?;
+            Ok((
+                input,
+                (
+                    sequence_number,
+                    width,
+                    height,
+                    x_offset,
+                    y_offset,
+                    delay_num,
+                    delay_den,
+                    dispose_op,
+                    blend_op,
+                ),
+            ))
+        }
+
+        let (
+            _,
+            (
+                sequence_number,
+                width,
+                height,
+                x_offset,
+                y_offset,
+                delay_num,
+                delay_den,
+                dispose_op,
+                blend_op,
+            ),
+        ) = parse_nom(input).map_err(|e| PngError::animation(Self::CHUNK_TYPE, e.to_string()))?;
+
+        let dispose_op = DisposeOp::from_u8(dispose_op).ok_or_else(|| {
+            PngError::animation(
+                Self::CHUNK_TYPE,
+                format!("Invalid fcTL dispose_op: {}", dispose_op),
+            )
+        })?;
+        let blend_op = BlendOp::from_u8(blend_op).ok_or_else(|| {
+            PngError::animation(
+                Self::CHUNK_TYPE,
+                format!("Invalid fcTL blend_op: {}", blend_op),
+            )
+        })?;
+
+        if width == 0 || height == 0 {
+            return Err(PngError::animation(
+                Self::CHUNK_TYPE,
+                "fcTL frame can't be empty",
+            ));
+        }
+        if x_offset as u64 + width as u64 > canvas_width as u64
+            || y_offset as u64 + height as u64 > canvas_height as u64
+        {
+            return Err(PngError::animation(
+                Self::CHUNK_TYPE,
+                format!("fcTL frame {} exceeds the image bounds", sequence_number),
+            ));
+        }
+
+        Ok(Self {
+            sequence_number,
+            width,
+            height,
+            x_offset,
+            y_offset,
+            delay_num,
+            delay_den,
+            dispose_op,
+            blend_op,
+        })
+    }
+
+    pub fn delay(&self) -> Duration {
+        // A denominator of 0 is to be treated as 100 (hundredths of a second)
+        let den = if self.delay_den == 0 {
+            100
+        } else {
+            self.delay_den
+        };
+        Duration::from_secs_f64(self.delay_num as f64 / den as f64)
+    }
+}
+
+fn parse_fdat(input: &[u8]) -> Result<(u32, &[u8])> {
+    let (data, sequence_number) = be_u32::<_, nom::error::Error<_>>(input)
+        .map_err(|e| PngError::animation(FDAT, e.to_string()))?;
+    Ok((sequence_number, data))
+}
+
+#[derive(Debug)]
+pub struct AnimationFrame {
+    pub control: FrameControl,
+    /// Inflated image data of the frame, `None` when the frame is the default image
+    pub data: Option<Vec<u8>>,
+    /// Offset of the fcTL chunk of the frame
+    pub offset: usize,
+}
+
+#[derive(Debug)]
+pub struct Animation {
+    pub control: AnimationControl,
+    pub frames: Vec<AnimationFrame>,
+}
+
+impl Animation {
+    /// Whether the IDAT image is the first frame of the animation
+    pub fn default_image_is_first_frame(&self) -> bool {
+        self.frames
+            .first()
+            .is_some_and(|frame| frame.data.is_none())
+    }
+}
+
+/// Removes the acTL, fcTL and fdAT chunks and builds the animation they describe.
+/// Must be called before the IDAT chunks are taken.
+pub fn take_animation(
+    chunks: &mut Vec<RawChunk>,
+    canvas_width: u32,
+    canvas_height: u32,
+    limits: &DecodeLimits,
+) -> Result<Option<Animation>> {
+    let Some(actl_index) = chunks
+        .iter()
+        .position(|c| c.chunk_type == AnimationControl::CHUNK_TYPE)
+    else {
+        return Ok(None);
+    };
+    let actl = chunks.remove(actl_index);
+    let control = AnimationControl::parse(actl.data).map_err(|e| e.at_offset(actl.offset))?;
+
+    let mut frames: Vec<AnimationFrame> = Vec::new();
+    let mut compressed: Vec<Vec<u8>> = Vec::new();
+    let mut default_image_frames: Vec<bool> = Vec::new();
+    let mut frame_offsets: Vec<usize> = Vec::new();
+    let mut expected_sequence_number = 0;
+    let mut seen_idat = false;
+    let mut index = 0;
+    while index < chunks.len() {
+        let chunk_type = chunks[index].chunk_type;
+        let offset = chunks[index].offset;
+        if chunk_type == IDAT {
+            seen_idat = true;
+            index += 1;
+        } else if chunk_type == FrameControl::CHUNK_TYPE {
+            let fctl = FrameControl::parse(chunks[index].data, canvas_width, canvas_height)
+                .map_err(|e| e.at_offset(offset))?;
+            if fctl.sequence_number != expected_sequence_number {
+                return Err(PngError::animation(
+                    chunk_type,
+                    format!(
+                        "Invalid fcTL sequence number: {} expected {}",
+                        fctl.sequence_number, expected_sequence_number
+                    ),
+                )
+                .at_offset(offset));
+            }
+            expected_sequence_number += 1;
+
+            let is_default_image = !seen_idat;
+            if is_default_image
+                && (fctl.x_offset != 0
+                    || fctl.y_offset != 0
+                    || fctl.width != canvas_width
+                    || fctl.height != canvas_height)
+            {
+                return Err(PngError::animation(
+                    chunk_type,
+                    "fcTL of the default image must cover the whole image",
+                )
+                .at_offset(offset));
+            }
+
+            frames.push(AnimationFrame {
+                control: fctl,
+                data: None,
+                offset,
+            });
+            compressed.push(Vec::new());
+            default_image_frames.push(is_default_image);
+            frame_offsets.push(offset);
+            chunks.remove(index);
+        } else if chunk_type == FDAT {
+            let (sequence_number, data) =
+                parse_fdat(chunks[index].data).map_err(|e| e.at_offset(offset))?;
+            if sequence_number != expected_sequence_number {
+                return Err(PngError::animation(
+                    chunk_type,
+                    format!(
+                        "Invalid fdAT sequence number: {} expected {}",
+                        sequence_number, expected_sequence_number
+                    ),
+                )
+                .at_offset(offset));
+            }
+            expected_sequence_number += 1;
+
+            compressed
+                .last_mut()
+                .ok_or_else(|| {
+                    PngError::animation(chunk_type, "fdAT chunk before any fcTL chunk")
+                        .at_offset(offset)
+                })?
+                .extend_from_slice(data);
+            chunks.remove(index);
+        } else {
+            index += 1;
+        }
+    }
+
+    if frames.len() != control.num_frames as usize {
+        return Err(PngError::animation(
+            AnimationControl::CHUNK_TYPE,
+            format!(
+                "acTL declares {} frames but {} were found",
+                control.num_frames,
+                frames.len()
+            ),
+        )
+        .at_offset(actl.offset));
+    }
+
+    for (((frame, compressed), is_default_image), offset) in frames
+        .iter_mut()
+        .zip(compressed)
+        .zip(default_image_frames)
+        .zip(frame_offsets)
+    {
+        if is_default_image {
+            if !compressed.is_empty() {
+                return Err(
+                    PngError::animation(FDAT, "fdAT chunk belongs to the default image")
+                        .at_offset(offset),
+                );
+            }
+            continue;
+        }
+        if compressed.is_empty() {
+            return Err(PngError::animation(
+                FDAT,
+                format!(
+                    "APNG frame {} without fdAT data",
+                    frame.control.sequence_number
+                ),
+            )
+            .at_offset(offset));
+        }
+        frame.data = Some(
+            inflate_chunk(
+                &compressed,
+                FDAT,
+                "inflated bytes",
+                limits.max_inflated_bytes,
+            )
+            .map_err(|e| e.at_offset(offset))?,
+        );
+    }
+
+    Ok(Some(Animation { control, frames }))
+}
+
+pub struct Frame {
+    pub image: Image,
+    pub delay: Duration,
+}
+
+fn blend_over(dst: Pixel, src: Pixel) -> Pixel {
+    let (sr, sg, sb, sa) = src;
+    let (dr, dg, db, da) = dst;
+    if sa == 255 {
+        return src;
+    }
+    if sa == 0 {
+        return dst;
+    }
+    let sa = sa as f32 / 255.0;
+    let da = da as f32 / 255.0;
+    let out_a = sa + da * (1.0 - sa);
+    let blend = |s: u8, d: u8| -> u8 {
+        ((s as f32 * sa + d as f32 * da * (1.0 - sa)) / out_a).round() as u8
+    };
+    (
+        blend(sr, dr),
+        blend(sg, dg),
+        blend(sb, db),
+        (out_a * 255.0).round() as u8,
+    )
+}
+
+/// Renders `frame_image` onto the canvas at the position described by `control`
+pub fn compose_frame(canvas: &mut Image, frame_image: &Image, control: &FrameControl) {
+    for y in 0..frame_image.height() {
+        for x in 0..frame_image.width() {
+            let (canvas_x, canvas_y) = (control.x_offset + x, control.y_offset + y);
+            let pixel = frame_image.pixel(x, y);
+            let pixel = match control.blend_op {
+                BlendOp::Source => pixel,
+                BlendOp::Over => blend_over(canvas.pixel(canvas_x, canvas_y), pixel),
+            };
+            canvas.set_pixel(canvas_x, canvas_y, pixel);
+        }
+    }
+}
+
+/// Applies the dispose op of a frame after it was shown
+pub fn dispose_frame(
+    canvas: &mut Image,
+    previous: Option<&Image>,
+    control: &FrameControl,
+    dispose_op: DisposeOp,
+) {
+    for y in control.y_offset..control.y_offset + control.height {
+        for x in control.x_offset..control.x_offset + control.width {
+            let pixel = match (dispose_op, previous) {
+                (DisposeOp::None, _) => continue,
+                (DisposeOp::Previous, Some(previous)) => previous.pixel(x, y),
+                (DisposeOp::Background | DisposeOp::Previous, _) => (0, 0, 0, 0),
+            };
+            canvas.set_pixel(x, y, pixel);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::write_chunk;
+    use crate::png_parser::{Png, MAGIC_NUMBER};
+
+    const RED: Pixel = (255, 0, 0, 255);
+    const WHITE: Pixel = (255, 255, 255, 255);
+    const GREEN: Pixel = (0, 255, 0, 255);
+    const TRANSPARENT: Pixel = (0, 0, 0, 0);
+
+    /// Zlib compressed RGBA scanlines with no filtering
+    fn image_data(width: usize, pixels: &[Pixel]) -> Vec<u8> {
+        let mut data = Vec::new();
+        for row in pixels.chunks(width) {
+            data.push(0);
+            for &(r, g, b, a) in row {
+                data.extend([r, g, b, a]);
+            }
+        }
+        deflate::deflate_bytes_zlib(&data)
+    }
+
+    fn frame_control(
+        sequence_number: u32,
+        (width, height): (u32, u32),
+        (x_offset, y_offset): (u32, u32),
+        dispose_op: u8,
+        blend_op: u8,
+    ) -> Vec<u8> {
+        let mut data = Vec::new();
+        for value in [sequence_number, width, height, x_offset, y_offset] {
+            data.extend(value.to_be_bytes());
+        }
+        data.extend(1u16.to_be_bytes());
+        data.extend(10u16.to_be_bytes());
+        data.extend([dispose_op, blend_op]);
+        data
+    }
+
+    /// A 2x2 red image followed by 1x1 frames exercising every dispose and blend op
+    fn animated_png() -> Vec<u8> {
+        let mut png = MAGIC_NUMBER.to_vec();
+        let mut ihdr = Vec::new();
+        ihdr.extend(2u32.to_be_bytes());
+        ihdr.extend(2u32.to_be_bytes());
+        ihdr.extend([8, 6, 0, 0, 0]);
+        write_chunk(&mut png, "IHDR", &ihdr).unwrap();
+        let mut actl = 4u32.to_be_bytes().to_vec();
+        actl.extend(0u32.to_be_bytes());
+        write_chunk(&mut png, AnimationControl::CHUNK_TYPE, &actl).unwrap();
+
+        // Kept on the canvas
+        let fctl = frame_control(0, (2, 2), (0, 0), 0, 0);
+        write_chunk(&mut png, FrameControl::CHUNK_TYPE, &fctl).unwrap();
+        write_chunk(&mut png, IDAT, &image_data(2, &[RED; 4])).unwrap();
+
+        // Offset, dispose op, blend op and pixel of every frame
+        let frames: [(_, _, _, Pixel); 3] = [
+            // Blended over a red pixel, then restored
+            ((1, 1), 2, 1, (0, 0, 255, 128)),
+            // Replaces a red pixel, then cleared
+            ((0, 0), 1, 0, GREEN),
+            ((1, 0), 0, 0, WHITE),
+        ];
+        let mut sequence_number = 1;
+        for (offset, dispose_op, blend_op, pixel) in frames {
+            let fctl = frame_control(sequence_number, (1, 1), offset, dispose_op, blend_op);
+            write_chunk(&mut png, FrameControl::CHUNK_TYPE, &fctl).unwrap();
+            let mut fdat = (sequence_number + 1).to_be_bytes().to_vec();
+            fdat.extend(image_data(1, &[pixel]));
+            write_chunk(&mut png, FDAT, &fdat).unwrap();
+            sequence_number += 2;
+        }
+        write_chunk(&mut png, "IEND", &[]).unwrap();
+        png
+    }
+
+    #[test]
+    fn frames_are_blended_and_disposed() {
+        let png = animated_png();
+        let png = Png::new(&png).unwrap();
+        let frames = png.get_frames().unwrap();
+        let pixels: Vec<Vec<Pixel>> = frames
+            .iter()
+            .map(|frame| {
+                [(0, 0), (1, 0), (0, 1), (1, 1)]
+                    .map(|(x, y)| frame.image.pixel(x, y))
+                    .to_vec()
+            })
+            .collect();
+        assert_eq!(
+            pixels,
+            [
+                vec![RED; 4],
+                vec![RED, RED, RED, (127, 0, 128, 255)],
+                vec![GREEN, RED, RED, RED],
+                vec![TRANSPARENT, WHITE, RED, RED],
+            ]
+        );
+        assert_eq!(frames[0].delay, Duration::from_millis(100));
+    }
+}
@@ -1,15 +1,74 @@
-use anyhow::Context;
+use std::io::Write;
+
 use nom::{bytes::complete::take, number::complete::be_u32, IResult};
 
+use crate::error::{PngError, Result};
+use crate::png_parser::IEND;
+
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct RawChunk<'a> {
     pub chunk_type: &'a str,
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "length", serialize_with = "crate::chunk::serialize_len")
+    )]
     pub data: &'a [u8],
+    /// Byte offset of the start of the chunk in the file
+    pub offset: usize,
+}
+
+/// Bit 5 of the `index`th byte of the chunk type, set for lowercase letters
+fn property_bit(chunk_type: &str, index: usize) -> bool {
+    chunk_type.as_bytes()[index] & 0x20 != 0
+}
+
+/// Whether chunks of this type can be kept by editors that modify critical chunks,
+/// see [`RawChunk::is_safe_to_copy`]
+pub fn is_safe_to_copy(chunk_type: &str) -> bool {
+    property_bit(chunk_type, 3)
+}
+
+impl RawChunk<'_> {
+    fn property_bit(&self, index: usize) -> bool {
+        property_bit(self.chunk_type, index)
+    }
+
+    /// Ancillary chunks can be ignored by decoders that don't recognize them,
+    /// critical chunks can't
+    pub fn is_ancillary(&self) -> bool {
+        self.property_bit(0)
+    }
+
+    /// Private chunks aren't defined by the spec or registered
+    pub fn is_private(&self) -> bool {
+        self.property_bit(1)
+    }
+
+    /// Must be unset in chunks conforming to the current version of the spec
+    pub fn is_reserved(&self) -> bool {
+        self.property_bit(2)
+    }
+
+    /// Safe to copy chunks don't depend on the image data and can be kept by editors
+    /// that modify critical chunks without recognizing them
+    pub fn is_safe_to_copy(&self) -> bool {
+        is_safe_to_copy(self.chunk_type)
+    }
 }
 
-fn parse_chunk(input: &[u8]) -> anyhow::Result<(&[u8], RawChunk)> {
+/// Serializes byte blobs as their length, their content is of no use in metadata dumps
+#[cfg(feature = "serde")]
+pub(crate) fn serialize_len<S: serde::Serializer>(
+    bytes: &impl AsRef<[u8]>,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.serialize_u64(bytes.as_ref().len() as u64)
+}
+
+fn parse_chunk(input: &[u8], offset: usize) -> Result<(&[u8], RawChunk<'_>)> {
     type ChunkValues<'a> = (&'a [u8], &'a [u8], u32);
-    fn parse_nom(input: &[u8]) -> IResult<&[u8], ChunkValues> {
+    fn parse_nom(input: &[u8]) -> IResult<&[u8], ChunkValues<'_>> {
         let (input, length) = be_u32(input)?;
         let (input, chunk_type) = take(4usize)(input)?;
         let (input, data) = take(length)(input)?;
@@ -17,35 +76,142 @@ fn parse_chunk(input: &[u8]) -> anyhow::Result<(&[u8], RawChunk)> {
         Ok((input, (chunk_type, data, crc)))
     }
 
-    let (input, (chunk_type, data, crc)) = parse_nom(input)
-        .map_err(|e| e.to_owned())
-        .context("Failed parsing chunk")?;
+    let (input, (chunk_type, data, crc)) =
+        parse_nom(input).map_err(|_| PngError::Truncated { offset })?;
 
     let calculated_crc = calculate_crc(chunk_type, data);
-    let chunk_type = std::str::from_utf8(chunk_type)?;
+    let chunk_type = parse_chunk_type(chunk_type, offset)?;
     if crc != calculated_crc {
-        anyhow::bail!("Invalid crc in chunk: {:?}", chunk_type);
+        return Err(PngError::Crc {
+            chunk_type: chunk_type.to_string(),
+            offset,
+        });
     }
 
-    Ok((input, RawChunk { chunk_type, data }))
+    Ok((
+        input,
+        RawChunk {
+            chunk_type,
+            data,
+            offset,
+        },
+    ))
+}
+
+/// Chunk types are restricted to ASCII letters
+pub(crate) fn parse_chunk_type(chunk_type: &[u8], offset: usize) -> Result<&str> {
+    if !chunk_type.iter().all(u8::is_ascii_alphabetic) {
+        return Err(PngError::InvalidChunkType {
+            chunk_type: chunk_type.to_vec(),
+            offset,
+        });
+    }
+    Ok(std::str::from_utf8(chunk_type).expect("ASCII letters are valid utf8"))
 }
 
-fn calculate_crc(chunk_type: &[u8], chunk_data: &[u8]) -> u32 {
+pub(crate) fn calculate_crc(chunk_type: &[u8], chunk_data: &[u8]) -> u32 {
     let mut hasher = crc32fast::Hasher::new();
     hasher.update(chunk_type);
     hasher.update(chunk_data);
     hasher.finalize()
 }
 
-pub fn parse_chunks(input: &[u8]) -> anyhow::Result<Vec<RawChunk>> {
+/// Parses consecutive chunks, `offset` is the position of `input` in the file
+pub fn parse_chunks(input: &[u8], offset: usize) -> Result<Vec<RawChunk<'_>>> {
     let mut chunks = Vec::new();
     let mut remaining_input = input;
 
     while !remaining_input.is_empty() {
-        let (rem, chunk) = parse_chunk(remaining_input)?;
+        let chunk_offset = offset + input.len() - remaining_input.len();
+        let (rem, chunk) = parse_chunk(remaining_input, chunk_offset)?;
         remaining_input = rem;
         chunks.push(chunk);
     }
 
     Ok(chunks)
 }
+
+pub fn write_chunk<W: Write>(writer: &mut W, chunk_type: &str, data: &[u8]) -> std::io::Result<()> {
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    writer.write_all(chunk_type.as_bytes())?;
+    writer.write_all(data)?;
+    writer.write_all(&calculate_crc(chunk_type.as_bytes(), data).to_be_bytes())
+}
+
+/// Parses consecutive chunks like [`parse_chunks`], skipping what can't be read instead of
+/// failing. Ancillary chunks with an invalid crc are dropped while critical ones are kept,
+/// a truncated chunk keeps the data that is present, and parsing stops after IEND or at the
+/// first unreadable chunk. Every problem is returned as a warning.
+pub fn parse_chunks_lenient(input: &[u8], offset: usize) -> (Vec<RawChunk<'_>>, Vec<PngError>) {
+    fn parse_header(input: &[u8]) -> IResult<&[u8], (u32, &[u8])> {
+        let (input, length) = be_u32(input)?;
+        let (input, chunk_type) = take(4usize)(input)?;
+        Ok((input, (length, chunk_type)))
+    }
+
+    let mut chunks = Vec::new();
+    let mut warnings = Vec::new();
+    let mut remaining_input = input;
+
+    while !remaining_input.is_empty() {
+        let chunk_offset = offset + input.len() - remaining_input.len();
+        let Ok((rest, (length, chunk_type))) = parse_header(remaining_input) else {
+            warnings.push(PngError::Truncated {
+                offset: chunk_offset,
+            });
+            break;
+        };
+        let chunk_type = match parse_chunk_type(chunk_type, chunk_offset) {
+            Ok(chunk_type) => chunk_type,
+            Err(_) => {
+                warnings.push(PngError::TrailingData {
+                    offset: chunk_offset,
+                });
+                break;
+            }
+        };
+
+        let length = length as usize;
+        if rest.len() < length + 4 {
+            warnings.push(PngError::Truncated {
+                offset: chunk_offset,
+            });
+            chunks.push(RawChunk {
+                chunk_type,
+                data: &rest[..length.min(rest.len())],
+                offset: chunk_offset,
+            });
+            break;
+        }
+        let (data, rest) = rest.split_at(length);
+        let crc = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]);
+        remaining_input = &rest[4..];
+
+        let chunk = RawChunk {
+            chunk_type,
+            data,
+            offset: chunk_offset,
+        };
+        if crc != calculate_crc(chunk_type.as_bytes(), data) {
+            warnings.push(PngError::Crc {
+                chunk_type: chunk_type.to_string(),
+                offset: chunk_offset,
+            });
+            if chunk.is_ancillary() {
+                continue;
+            }
+        }
+        chunks.push(chunk);
+
+        if chunk_type == IEND {
+            if !remaining_input.is_empty() {
+                warnings.push(PngError::TrailingData {
+                    offset: offset + input.len() - remaining_input.len(),
+                });
+            }
+            break;
+        }
+    }
+
+    (chunks, warnings)
+}
@@ -0,0 +1,170 @@
+use std::collections::HashSet;
+
+use crate::ancillary_chunks::background::Background;
+use crate::ancillary_chunks::chrm::Chromaticities;
+use crate::ancillary_chunks::cicp::CodingIndependentCodePoints;
+use crate::ancillary_chunks::clli::ContentLightLevel;
+use crate::ancillary_chunks::exif::Exif;
+use crate::ancillary_chunks::gama::Gama;
+use crate::ancillary_chunks::hist::Histogram;
+use crate::ancillary_chunks::iccp::IccProfileChunk;
+use crate::ancillary_chunks::mdcv::MasteringDisplayColorVolume;
+use crate::ancillary_chunks::offs::ImageOffset;
+use crate::ancillary_chunks::pcal::PixelCalibration;
+use crate::ancillary_chunks::phys::PhysicalUnits;
+use crate::ancillary_chunks::sbit::SignificantBits;
+use crate::ancillary_chunks::scal::PhysicalScale;
+use crate::ancillary_chunks::splt::SuggestedPalette;
+use crate::ancillary_chunks::srgb::Srgb;
+use crate::ancillary_chunks::ster::Stereo;
+use crate::ancillary_chunks::time::Time;
+use crate::apng::AnimationControl;
+use crate::chunk::RawChunk;
+use crate::error::{PngError, Result};
+use crate::ihdr::IHDR;
+use crate::plte::PLTE;
+use crate::png_parser::{IDAT, IEND, TRNS};
+
+/// How violations of the chunk ordering rules are handled
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum OrderValidation {
+    /// Violations are reported as warnings and decoding goes on
+    #[default]
+    WarnOnly,
+    /// The first violation aborts decoding
+    Strict,
+}
+
+/// Chunks that must appear before PLTE and IDAT
+pub(crate) const BEFORE_PLTE: [&str; 5] = [
+    Gama::CHUNK_TYPE,
+    Chromaticities::CHUNK_TYPE,
+    Srgb::CHUNK_TYPE,
+    IccProfileChunk::CHUNK_TYPE,
+    SignificantBits::CHUNK_TYPE,
+];
+
+/// Chunks that must appear after PLTE, if any, and before IDAT
+const AFTER_PLTE: [&str; 3] = [TRNS, Background::CHUNK_TYPE, Histogram::CHUNK_TYPE];
+
+/// Chunks that must appear before IDAT
+const BEFORE_IDAT: [&str; 12] = [
+    PhysicalUnits::CHUNK_TYPE,
+    SuggestedPalette::CHUNK_TYPE,
+    ImageOffset::CHUNK_TYPE,
+    PixelCalibration::CHUNK_TYPE,
+    PhysicalScale::CHUNK_TYPE,
+    Stereo::CHUNK_TYPE,
+    CodingIndependentCodePoints::CHUNK_TYPE,
+    MasteringDisplayColorVolume::CHUNK_TYPE,
+    MasteringDisplayColorVolume::LEGACY_CHUNK_TYPE,
+    ContentLightLevel::CHUNK_TYPE,
+    ContentLightLevel::LEGACY_CHUNK_TYPE,
+    AnimationControl::CHUNK_TYPE,
+];
+
+/// Chunks that can't appear more than once
+const SINGLE: [&str; 24] = [
+    IHDR,
+    PLTE,
+    IEND,
+    TRNS,
+    Gama::CHUNK_TYPE,
+    Chromaticities::CHUNK_TYPE,
+    Srgb::CHUNK_TYPE,
+    IccProfileChunk::CHUNK_TYPE,
+    SignificantBits::CHUNK_TYPE,
+    Background::CHUNK_TYPE,
+    Histogram::CHUNK_TYPE,
+    PhysicalUnits::CHUNK_TYPE,
+    Time::CHUNK_TYPE,
+    ImageOffset::CHUNK_TYPE,
+    PixelCalibration::CHUNK_TYPE,
+    PhysicalScale::CHUNK_TYPE,
+    Stereo::CHUNK_TYPE,
+    CodingIndependentCodePoints::CHUNK_TYPE,
+    MasteringDisplayColorVolume::CHUNK_TYPE,
+    MasteringDisplayColorVolume::LEGACY_CHUNK_TYPE,
+    ContentLightLevel::CHUNK_TYPE,
+    ContentLightLevel::LEGACY_CHUNK_TYPE,
+    Exif::CHUNK_TYPE,
+    AnimationControl::CHUNK_TYPE,
+];
+
+fn violation(chunk: &RawChunk, reason: String) -> PngError {
+    PngError::ChunkOrder {
+        chunk_type: chunk.chunk_type.to_string(),
+        offset: chunk.offset,
+        reason,
+    }
+}
+
+fn find_violations(chunks: &[RawChunk]) -> Vec<PngError> {
+    let mut violations = Vec::new();
+    let plte_index = chunks.iter().position(|c| c.chunk_type == PLTE);
+    let idat_index = chunks.iter().position(|c| c.chunk_type == IDAT);
+    let mut seen = HashSet::new();
+    let must_precede_idat = [&BEFORE_PLTE[..], &AFTER_PLTE, &BEFORE_IDAT].concat();
+
+    for (i, chunk) in chunks.iter().enumerate() {
+        let chunk_type = chunk.chunk_type;
+        let before_plte = plte_index.is_none_or(|plte| i < plte);
+        let before_idat = idat_index.is_none_or(|idat| i < idat);
+        let previous = i.checked_sub(1).map(|i| chunks[i].chunk_type);
+
+        if i == 0 && chunk_type != IHDR {
+            violations.push(violation(chunk, "IHDR must be the first chunk".to_string()));
+        }
+        if previous == Some(IEND) {
+            violations.push(violation(chunk, "Chunk after IEND".to_string()));
+        }
+        if SINGLE.contains(&chunk_type) && !seen.insert(chunk_type) {
+            violations.push(violation(
+                chunk,
+                format!("Only one {} chunk is allowed", chunk_type),
+            ));
+        }
+        if chunk_type == IDAT && idat_index.is_some_and(|idat| i > idat) && previous != Some(IDAT) {
+            violations.push(violation(
+                chunk,
+                "IDAT chunks must be consecutive".to_string(),
+            ));
+        }
+        if chunk_type == PLTE && !before_idat {
+            violations.push(violation(chunk, "PLTE must precede IDAT".to_string()));
+        }
+        if BEFORE_PLTE.contains(&chunk_type) && !before_plte {
+            violations.push(violation(
+                chunk,
+                format!("{} must precede PLTE", chunk_type),
+            ));
+        }
+        if chunk_type == Histogram::CHUNK_TYPE && plte_index.is_none() {
+            violations.push(violation(chunk, "hIST requires a PLTE chunk".to_string()));
+        }
+        if AFTER_PLTE.contains(&chunk_type) && plte_index.is_some() && before_plte {
+            violations.push(violation(chunk, format!("{} must follow PLTE", chunk_type)));
+        }
+        if must_precede_idat.contains(&chunk_type) && !before_idat {
+            violations.push(violation(
+                chunk,
+                format!("{} must precede IDAT", chunk_type),
+            ));
+        }
+    }
+    violations
+}
+
+/// Checks the position and count of every chunk against the rules of the spec.
+/// In strict mode the first violation is returned as an error,
+/// otherwise every violation is returned as a warning.
+pub fn validate_chunk_order(
+    chunks: &[RawChunk],
+    validation: OrderValidation,
+) -> Result<Vec<PngError>> {
+    let mut violations = find_violations(chunks);
+    match validation {
+        OrderValidation::Strict if !violations.is_empty() => Err(violations.remove(0)),
+        _ => Ok(violations),
+    }
+}
@@ -0,0 +1,108 @@
+/// Row major 3x3 matrix applied to column vectors
+pub type Matrix3 = [[f32; 3]; 3];
+
+/// CIE xy chromaticity of the D65 white point used by sRGB
+pub const D65: (f32, f32) = (0.3127, 0.3290);
+
+/// Converts linear sRGB to CIE XYZ
+pub const SRGB_TO_XYZ: Matrix3 = [
+    [0.4124564, 0.3575761, 0.1804375],
+    [0.2126729, 0.7151522, 0.0721750],
+    [0.0193339, 0.119192, 0.9503041],
+];
+
+/// Converts CIE XYZ to linear sRGB
+pub const XYZ_TO_SRGB: Matrix3 = [
+    [3.2404542, -1.5371385, -0.4985314],
+    [-0.969266, 1.8760108, 0.041556],
+    [0.0556434, -0.2040259, 1.0572252],
+];
+
+const BRADFORD: Matrix3 = [
+    [0.8951, 0.2664, -0.1614],
+    [-0.7502, 1.7135, 0.0367],
+    [0.0389, -0.0685, 1.0296],
+];
+
+pub fn multiply(a: &Matrix3, b: &Matrix3) -> Matrix3 {
+    let mut out = [[0.0; 3]; 3];
+    for (i, row) in out.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    out
+}
+
+pub fn apply(m: &Matrix3, v: [f32; 3]) -> [f32; 3] {
+    [0, 1, 2].map(|i| m[i][0] * v[0] + m[i][1] * v[1] + m[i][2] * v[2])
+}
+
+/// Returns `None` for singular matrices
+pub fn invert(m: &Matrix3) -> Option<Matrix3> {
+    let cofactor =
+        |r0: usize, r1: usize, c0: usize, c1: usize| m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0];
+    let det = m[0][0] * cofactor(1, 2, 1, 2) - m[0][1] * cofactor(1, 2, 0, 2)
+        + m[0][2] * cofactor(1, 2, 0, 1);
+    if det.abs() < f32::EPSILON {
+        return None;
+    }
+    Some([
+        [
+            cofactor(1, 2, 1, 2) / det,
+            -cofactor(0, 2, 1, 2) / det,
+            cofactor(0, 1, 1, 2) / det,
+        ],
+        [
+            -cofactor(1, 2, 0, 2) / det,
+            cofactor(0, 2, 0, 2) / det,
+            -cofactor(0, 1, 0, 2) / det,
+        ],
+        [
+            cofactor(1, 2, 0, 1) / det,
+            -cofactor(0, 2, 0, 1) / det,
+            cofactor(0, 1, 0, 1) / det,
+        ],
+    ])
+}
+
+/// XYZ of a chromaticity with a luminance of 1
+pub fn xy_to_xyz((x, y): (f32, f32)) -> [f32; 3] {
+    [x / y, 1.0, (1.0 - x - y) / y]
+}
+
+/// Chromatic adaptation of XYZ values from the `from` white point to the `to` white point
+pub fn bradford_adaptation(from: [f32; 3], to: [f32; 3]) -> Matrix3 {
+    let inverse = invert(&BRADFORD).expect("Bradford matrix is invertible");
+    let from = apply(&BRADFORD, from);
+    let to = apply(&BRADFORD, to);
+    let scale = [
+        [to[0] / from[0], 0.0, 0.0],
+        [0.0, to[1] / from[1], 0.0],
+        [0.0, 0.0, to[2] / from[2]],
+    ];
+    multiply(&inverse, &multiply(&scale, &BRADFORD))
+}
+
+/// Converts XYZ values relative to `white` to linear sRGB, adapting them to D65
+pub fn xyz_to_srgb(white: [f32; 3]) -> Matrix3 {
+    multiply(&XYZ_TO_SRGB, &bradford_adaptation(white, xy_to_xyz(D65)))
+}
+
+/// The sRGB transfer function, from a linear value in [0, 1] to an encoded one
+pub fn srgb_encode(linear: f32) -> f32 {
+    if linear <= 0.0031308 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// The inverse of [`srgb_encode`]
+pub fn srgb_decode(encoded: f32) -> f32 {
+    if encoded <= 0.04045 {
+        encoded / 12.92
+    } else {
+        ((encoded + 0.055) / 1.055).powf(2.4)
+    }
+}
@@ -1,12 +1,22 @@
-use anyhow::Context;
 use bitreader::BitReader;
 
-use crate::{plte::Palette, png_parser::Pixel, run_n};
+use crate::{
+    error::{PngError, Result},
+    plte::{Palette, PLTE},
+    png_parser::{pixel16_to_pixel, Pixel, Pixel16},
+    run_n,
+};
 
+/// Transparent colors are kept as raw samples in the bit depth of the image
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(tag = "type"))]
 pub enum ColorType {
-    Grayscale { transparent: Option<u8> },
-    Rgb { transparent: Option<(u8, u8, u8)> },
+    Grayscale {
+        transparent: Option<u16>,
+    },
+    Rgb {
+        transparent: Option<(u16, u16, u16)>,
+    },
     Palette(Palette),
     GrayscaleAlpha,
     Rgba,
@@ -15,6 +25,21 @@ pub fn map_pixel_value(bit_depth: u8, value: u8) -> u8 {
     ((value as f32 / (2.0f32.powf(bit_depth as f32) - 1.0)) * 255.0) as u8
 }
 
+/// Scales a sample of `bit_depth` bits to the full 16 bit range
+pub fn map_pixel_value16(bit_depth: u8, value: u16) -> u16 {
+    let max = (1u32 << bit_depth) - 1;
+    (value as u32 * u16::MAX as u32 / max) as u16
+}
+
+/// Inverse of [`map_pixel_value`], maps an 8 bit value back to a sample of `bit_depth` bits
+pub fn unmap_pixel_value(bit_depth: u8, value: u8) -> u16 {
+    if bit_depth == 16 {
+        (value as u16) << 8 | value as u16
+    } else {
+        ((value as f32 / 255.0) * (2.0f32.powf(bit_depth as f32) - 1.0)).round() as u16
+    }
+}
+
 impl ColorType {
     pub fn valid_bit_depths(&self) -> Vec<u8> {
         match self {
@@ -31,20 +56,23 @@ impl ColorType {
         bit_depth: u8,
         plte: Option<Palette>,
         trns_content: Option<&[u8]>,
-    ) -> anyhow::Result<ColorType> {
-        let read_trns_value = |buf: [u8; 2]| -> anyhow::Result<u8> {
+    ) -> Result<ColorType> {
+        const TRNS: &str = "tRNS";
+        let read_trns_value = |buf: [u8; 2]| -> Result<u16> {
             let v = u16::from_be_bytes(buf);
-            if bit_depth <= 8 {
-                Ok(map_pixel_value(bit_depth, v as u8))
-            } else {
-                Ok((v >> 8) as u8)
+            if bit_depth < 16 && v >> bit_depth != 0 {
+                return Err(PngError::ancillary(
+                    TRNS,
+                    format!("Transparent value {} exceeds bit depth {}", v, bit_depth),
+                ));
             }
+            Ok(v)
         };
         match value {
             0 => {
                 let transparent = if let Some(trns_content) = trns_content {
                     if trns_content.len() != 2 {
-                        anyhow::bail!("Invalid transparent buffer len")
+                        return Err(PngError::ancillary(TRNS, "Invalid transparent buffer len"));
                     }
                     let buf = (&trns_content[..2])
                         .try_into()
@@ -60,7 +88,7 @@ impl ColorType {
             2 => {
                 let pixel = if let Some(trns_content) = trns_content {
                     if trns_content.len() != 6 {
-                        anyhow::bail!("Invalid transparent buffer len")
+                        return Err(PngError::ancillary(TRNS, "Invalid transparent buffer len"));
                     }
 
                     let r =
@@ -77,14 +105,47 @@ impl ColorType {
                 Ok(ColorType::Rgb { transparent: pixel })
             }
             3 => {
-                let plte = plte.context("Expected palette for ColorType 3 but no one was found")?;
+                let plte = plte.ok_or(PngError::MissingChunk(PLTE))?;
                 Ok(ColorType::Palette(plte))
             }
             4 => Ok(ColorType::GrayscaleAlpha),
             6 => Ok(ColorType::Rgba),
-            e => Err(anyhow::anyhow!("Invalid color type: {}", e)),
+            e => Err(PngError::ihdr(format!("Invalid color type: {}", e))),
+        }
+    }
+    pub fn to_u8(&self) -> u8 {
+        match self {
+            ColorType::Grayscale { .. } => 0,
+            ColorType::Rgb { .. } => 2,
+            ColorType::Palette(_) => 3,
+            ColorType::GrayscaleAlpha => 4,
+            ColorType::Rgba => 6,
         }
     }
+
+    /// tRNS content for grayscale and rgb images, palette transparency is part of the [`Palette`]
+    pub fn trns_bytes(&self) -> Option<Vec<u8>> {
+        match self {
+            ColorType::Grayscale {
+                transparent: Some(gray),
+            } => Some(gray.to_be_bytes().to_vec()),
+            ColorType::Rgb {
+                transparent: Some((r, g, b)),
+            } => Some([r, g, b].iter().flat_map(|v| v.to_be_bytes()).collect()),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColorType::Grayscale { .. } => "grayscale",
+            ColorType::Rgb { .. } => "RGB",
+            ColorType::Palette(_) => "indexed",
+            ColorType::GrayscaleAlpha => "grayscale with alpha",
+            ColorType::Rgba => "RGB with alpha",
+        }
+    }
+
     pub fn values_per_pixel(&self) -> u8 {
         match &self {
             ColorType::Grayscale { .. } => 1,
@@ -95,103 +156,55 @@ impl ColorType {
         }
     }
 
-    pub fn read_pixel(
-        &self,
-        bit_depth: u8,
-        scanline_reader: &mut BitReader,
-    ) -> anyhow::Result<Pixel> {
-        let read_and_map_u8 = |scanline: &mut BitReader| -> anyhow::Result<u8> {
-            let v = scanline.read_u8(bit_depth)?;
-
-            let v = if bit_depth != 8 {
-                map_pixel_value(bit_depth, v)
-            } else {
-                v
-            };
-            Ok(v)
-        };
-
-        let read_and_map_u16 = |scanline: &mut BitReader| -> anyhow::Result<u8> {
-            let v = (scanline.read_u16(bit_depth)? >> 8) as u8;
-            Ok(v)
-        };
+    /// Reads a pixel keeping the full sample precision, samples are scaled to 16 bits
+    pub fn read_pixel16(&self, bit_depth: u8, scanline_reader: &mut BitReader) -> Result<Pixel16> {
+        let read_sample =
+            |scanline: &mut BitReader| -> Result<u16> { Ok(scanline.read_u16(bit_depth)?) };
+        let map = |v: u16| map_pixel_value16(bit_depth, v);
 
         let pixel = match &self {
             ColorType::Grayscale { transparent } => {
-                let grayscale = if bit_depth <= 8 {
-                    read_and_map_u8(scanline_reader)?
-                } else if bit_depth == 16 {
-                    read_and_map_u16(scanline_reader)?
+                let grayscale = read_sample(scanline_reader)?;
+                let alpha = if *transparent == Some(grayscale) {
+                    0
                 } else {
-                    unreachable!("Invalid bitdepth")
+                    u16::MAX
                 };
+                let grayscale = map(grayscale);
 
-                (
-                    grayscale,
-                    grayscale,
-                    grayscale,
-                    if let Some(transparent) = transparent {
-                        if *transparent == grayscale {
-                            0
-                        } else {
-                            255
-                        }
-                    } else {
-                        255
-                    },
-                )
+                (grayscale, grayscale, grayscale, alpha)
             }
             ColorType::Rgb { transparent } => {
-                let pixel = if bit_depth == 8 {
-                    run_n!(3, scanline_reader.read_u8(8)?)
-                } else if bit_depth == 16 {
-                    run_n!(3, read_and_map_u16(scanline_reader)?)
-                } else {
-                    unreachable!("Invalid bitdepth")
-                };
-                let alpha = if let Some(transparent) = transparent {
-                    if *transparent == pixel {
-                        0
-                    } else {
-                        255
-                    }
+                let pixel = run_n!(3, read_sample(scanline_reader)?);
+                let alpha = if *transparent == Some(pixel) {
+                    0
                 } else {
-                    255
+                    u16::MAX
                 };
 
                 let (r, g, b) = pixel;
-                (r, g, b, alpha)
+                (map(r), map(g), map(b), alpha)
             }
             ColorType::Palette(Palette { entries }) => {
-                if bit_depth <= 8 {
-                    let index = scanline_reader.read_u8(bit_depth)?;
-                    entries[index as usize]
-                } else {
-                    unreachable!("Invalid bitdepth")
-                }
+                let index = scanline_reader.read_u8(bit_depth)?;
+                let (r, g, b, a) = *entries.get(index as usize).ok_or_else(|| {
+                    PngError::image_data(format!("Palette index out of range: {}", index))
+                })?;
+                let widen = |v: u8| v as u16 * 257;
+                (widen(r), widen(g), widen(b), widen(a))
             }
-
             ColorType::GrayscaleAlpha => {
-                let (gray_scale, alpha) = if bit_depth == 8 {
-                    run_n!(2, scanline_reader.read_u8(8)?)
-                } else if bit_depth == 16 {
-                    run_n!(2, read_and_map_u16(scanline_reader)?)
-                } else {
-                    unreachable!("Invalid bitdepth")
-                };
+                let (gray_scale, alpha) = run_n!(2, map(read_sample(scanline_reader)?));
 
                 (gray_scale, gray_scale, gray_scale, alpha)
             }
-            ColorType::Rgba => {
-                if bit_depth == 8 {
-                    run_n!(4, scanline_reader.read_u8(8)?)
-                } else if bit_depth == 16 {
-                    run_n!(4, read_and_map_u16(scanline_reader)?)
-                } else {
-                    unreachable!("Invalid bitdepth")
-                }
-            }
+            ColorType::Rgba => run_n!(4, map(read_sample(scanline_reader)?)),
         };
         Ok(pixel)
     }
+
+    pub fn read_pixel(&self, bit_depth: u8, scanline_reader: &mut BitReader) -> Result<Pixel> {
+        self.read_pixel16(bit_depth, scanline_reader)
+            .map(pixel16_to_pixel)
+    }
 }
@@ -1,8 +1,19 @@
+use std::io::Read;
 use std::time::{Duration, Instant};
 
 use minifb::{Key, Window, WindowOptions};
 
+use crate::ancillary_chunks::chrm::Chromaticities;
+use crate::ancillary_chunks::exif::Orientation;
 use crate::ancillary_chunks::gama::Gama;
+use crate::ancillary_chunks::srgb::Srgb;
+use crate::ancillary_chunks::ster::{Stereo, StereoMode};
+use crate::apng::Frame;
+use crate::color::{self, Matrix3};
+use crate::hdr::ToneMapper;
+use crate::icc::IccTransform;
+use crate::png_parser::{Image, Image16};
+use crate::stream_decoder::{Row, StreamDecoder};
 fn rgb_to_hex(r: u32, g: u32, b: u32) -> u32 {
     (r << 16) | (g << 8) | b
 }
@@ -10,31 +21,116 @@ fn rgb_to_hex(r: u32, g: u32, b: u32) -> u32 {
 fn lerp(a: u32, b: u32, t: f32) -> u32 {
     (a as f32 * t + b as f32 * (1.0 - t)) as u32
 }
-fn apply_gama(v: u8, gama: Option<Gama>) -> u8 {
-    if let Some(Gama(gama)) = gama {
-        let v = v as f32;
-        let v = v / 255.0;
-        let v = v.powf(1.0 / gama);
-        let v = v * 255.0;
-        v as u8
-    } else {
-        v
+
+/// How decoded images are turned into window pixels
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    /// Scale of the image height
+    pub scale: f32,
+    /// Width of a pixel divided by its height, the width is stretched by it
+    pub pixel_aspect_ratio: f32,
+    pub background: Option<(u8, u8, u8)>,
+    pub gama: Option<Gama>,
+    /// Primaries of the image, converted to sRGB before display when set
+    pub chromaticities: Option<Chromaticities>,
+    /// The image is sRGB, `gama` and `chromaticities` are ignored
+    pub srgb: Option<Srgb>,
+    /// Conversion from the embedded ICC profile, overrides all of the above
+    pub icc: Option<IccTransform>,
+    /// The image is rotated or flipped into this orientation before display
+    pub orientation: Option<Orientation>,
+    /// The stereo pair of the image and the layout it is shown in
+    pub stereo: Option<(Stereo, StereoMode)>,
+    /// Any key closes the window, not just escape
+    pub wait_for_input: bool,
+}
+
+/// Maps the samples of an image to the sRGB display
+struct ColorTransform {
+    /// Linear light value of every 8 bit sample, per channel
+    to_linear: [[f32; 256]; 3],
+    to_srgb: Option<Matrix3>,
+    /// Samples are already sRGB and are displayed as is
+    identity: bool,
+}
+
+impl ColorTransform {
+    fn new(options: &RenderOptions) -> Self {
+        if let Some(icc) = &options.icc {
+            return Self {
+                to_linear: std::array::from_fn(|i| {
+                    std::array::from_fn(|v| icc.curves[i].eval(v as f32 / 255.0))
+                }),
+                to_srgb: icc.to_srgb,
+                identity: false,
+            };
+        }
+        // sRGB overrides gAMA and cHRM, its samples only go through the sRGB curve
+        let (gama, chromaticities) = match options.srgb {
+            Some(_) => (None, None),
+            None => (options.gama, options.chromaticities),
+        };
+        let to_linear = [std::array::from_fn(|v| {
+            let v = v as f32 / 255.0;
+            match gama {
+                Some(Gama(gama)) => v.powf(1.0 / gama),
+                None => color::srgb_decode(v),
+            }
+        }); 3];
+        let to_srgb = chromaticities.and_then(|c| c.to_srgb());
+        Self {
+            to_linear,
+            to_srgb,
+            identity: gama.is_none() && to_srgb.is_none(),
+        }
+    }
+
+    fn apply(&self, (r, g, b): (u8, u8, u8)) -> (u8, u8, u8) {
+        let linear = [r, g, b];
+        let linear = [0, 1, 2].map(|i| self.to_linear[i][linear[i] as usize]);
+        let linear = match &self.to_srgb {
+            Some(to_srgb) => color::apply(to_srgb, linear),
+            None => linear,
+        };
+        let [r, g, b] =
+            linear.map(|v| (color::srgb_encode(v.clamp(0.0, 1.0)) * 255.0).round() as u8);
+        (r, g, b)
     }
 }
 
-pub fn display_image(
-    image_data: Vec<Vec<(u8, u8, u8, u8)>>,
-    scale: f32,
-    timeout: Option<Duration>,
-    background: Option<(u8, u8, u8)>,
-    gama: Option<Gama>,
-) -> anyhow::Result<()> {
-    let height = image_data.len();
-    let width = image_data[0].len();
+fn render_image(image_data: &Image, options: &RenderOptions) -> (Vec<u32>, usize, usize) {
+    let RenderOptions {
+        scale, background, ..
+    } = *options;
+    let transform = ColorTransform::new(options);
+    let rearranged;
+    let image_data = match options.stereo {
+        Some((stereo, mode)) if stereo.mode != mode => {
+            rearranged = stereo.to_mode(image_data, mode);
+            &rearranged
+        }
+        _ => image_data,
+    };
+    let oriented;
+    let image_data = match options.orientation {
+        Some(orientation) if orientation != Orientation::TopLeft => {
+            oriented = orientation.apply(image_data);
+            &oriented
+        }
+        _ => image_data,
+    };
+    // Pixels turned on their side stretch the other axis
+    let scale_x = match options.orientation {
+        Some(orientation) if orientation.swaps_dimensions() => scale / options.pixel_aspect_ratio,
+        _ => scale * options.pixel_aspect_ratio,
+    };
+
+    let height = image_data.height() as usize;
+    let width = image_data.width() as usize;
     let grid_size = 10;
 
     // Calculate new dimensions
-    let new_width = (width as f32 * scale).ceil() as usize;
+    let new_width = (width as f32 * scale_x).ceil() as usize;
     let new_height = (height as f32 * scale).ceil() as usize;
 
     // Create a buffer for the image with grid background
@@ -43,16 +139,15 @@ pub fn display_image(
     for new_y in 0..new_height {
         for new_x in 0..new_width {
             // Map the new coordinates back to the original image using nearest-neighbor scaling
-            let orig_x = (new_x as f32 / scale).floor() as usize;
+            let orig_x = (new_x as f32 / scale_x).floor() as usize;
             let orig_y = (new_y as f32 / scale).floor() as usize;
 
-            let (r, g, b, a) = image_data[orig_y][orig_x];
-            let (r, g, b, a) = (
-                apply_gama(r, gama),
-                apply_gama(g, gama),
-                apply_gama(b, gama),
-                a,
-            );
+            let (r, g, b, a) = image_data.pixel(orig_x as u32, orig_y as u32);
+            let (r, g, b) = if transform.identity {
+                (r, g, b)
+            } else {
+                transform.apply((r, g, b))
+            };
 
             // Determine if this pixel is part of the grid pattern
             let is_grid = ((new_x / grid_size) % 2 == 0 && (new_y / grid_size) % 2 == 0)
@@ -89,6 +184,43 @@ pub fn display_image(
             buffer[new_y * new_width + new_x] = pixel;
         }
     }
+    (buffer, new_width, new_height)
+}
+
+/// The timeout of an animation is extended so it plays through, animations that loop forever
+/// are never timed out
+fn playback_timeout(
+    frames: &[Frame],
+    num_plays: u32,
+    timeout: Option<Duration>,
+) -> Option<Duration> {
+    let timeout = timeout?;
+    if frames.len() <= 1 {
+        return Some(timeout);
+    }
+    if num_plays == 0 {
+        return None;
+    }
+    let duration = frames.iter().map(|frame| frame.delay).sum::<Duration>() * num_plays;
+    Some(timeout.max(duration))
+}
+
+/// Displays the frames one after the other, waiting each frame's delay.
+/// The animation is repeated `num_plays` times (0 loops forever) and then stays on the last frame.
+pub fn display_image(
+    frames: Vec<Frame>,
+    num_plays: u32,
+    timeout: Option<Duration>,
+    options: &RenderOptions,
+) -> anyhow::Result<()> {
+    let (buffers, sizes): (Vec<_>, Vec<_>) = frames
+        .iter()
+        .map(|frame| {
+            let (buffer, width, height) = render_image(&frame.image, options);
+            (buffer, (width, height))
+        })
+        .unzip();
+    let (new_width, new_height) = sizes[0];
 
     // Create a window to display the image
     let mut window = Window::new(
@@ -98,15 +230,202 @@ pub fn display_image(
         WindowOptions::default(),
     )?;
 
+    let timeout = playback_timeout(&frames, num_plays, timeout);
     let start_time = Instant::now();
+    let mut frame_index = 0;
+    let mut plays = 0;
+    let mut frame_start = Instant::now();
     // Display the image
-    while window.is_open() && !window.is_key_down(Key::Escape) {
-        window.update_with_buffer(&buffer, new_width, new_height)?;
-        if let Some(timeout) = timeout {
-            if (Instant::now() - start_time) > timeout {
-                break;
+    while window.is_open() && !closed_by_user(&window, options) {
+        window.update_with_buffer(&buffers[frame_index], new_width, new_height)?;
+
+        let animation_done = num_plays != 0 && plays >= num_plays;
+        if buffers.len() > 1
+            && !animation_done
+            && frame_start.elapsed() >= frames[frame_index].delay
+        {
+            frame_start = Instant::now();
+            if frame_index + 1 == buffers.len() {
+                plays += 1;
+                if num_plays == 0 || plays < num_plays {
+                    frame_index = 0;
+                }
+            } else {
+                frame_index += 1;
             }
         }
+
+        if timed_out(start_time, timeout) {
+            break;
+        }
     }
     Ok(())
 }
+
+/// Displays an HDR image after tone mapping it to SDR, the color fields of the options are
+/// ignored since cICP takes precedence over every other color chunk
+pub fn display_hdr_image(
+    image: &Image16,
+    tone_mapper: &ToneMapper,
+    timeout: Option<Duration>,
+    options: &RenderOptions,
+) -> anyhow::Result<()> {
+    let frame = Frame {
+        image: tone_mapper.tone_map(image),
+        delay: Duration::ZERO,
+    };
+    let options = RenderOptions {
+        gama: None,
+        chromaticities: None,
+        srgb: None,
+        icc: None,
+        ..options.clone()
+    };
+    display_image(vec![frame], 1, timeout, &options)
+}
+
+fn closed_by_user(window: &Window, options: &RenderOptions) -> bool {
+    window.is_key_down(Key::Escape) || (options.wait_for_input && !window.get_keys().is_empty())
+}
+
+fn timed_out(start_time: Instant, timeout: Option<Duration>) -> bool {
+    timeout.is_some_and(|timeout| start_time.elapsed() > timeout)
+}
+
+/// Rows decoded between two checks of the window events, so long passes can be interrupted
+const EVENT_POLL_ROWS: usize = 64;
+
+/// Size of the block every pixel of an Adam7 pass is replicated over
+/// until the following passes fill it in
+const ADAM7_BLOCKS: [(u32, u32); 7] = [(8, 8), (4, 8), (4, 4), (2, 4), (2, 2), (1, 2), (1, 1)];
+
+fn fill_row(image: &mut Image, row: &Row) {
+    let (block_width, block_height) = row.pass.map_or((1, 1), |pass| ADAM7_BLOCKS[pass]);
+    let block_bottom = (row.y + block_height).min(image.height());
+    for i in 0..row.width() {
+        let s = &row.samples[i * 4..i * 4 + 4];
+        let pixel = (s[0], s[1], s[2], s[3]);
+        let x = row.x_start + i as u32 * row.x_step;
+        let block_right = (x + block_width).min(image.width());
+        for y in row.y..block_bottom {
+            for x in x..block_right {
+                image.set_pixel(x, y, pixel);
+            }
+        }
+    }
+}
+
+/// Displays the image while it is being decoded, every Adam7 pass is shown as soon as it is
+/// complete with the missing pixels filled by block replication.
+/// If decoding fails midway the partial image stays on screen and the error is returned once
+/// the window is closed. Closing the window stops decoding.
+pub fn display_progressive<R: Read>(
+    mut decoder: StreamDecoder<R>,
+    timeout: Option<Duration>,
+    options: &RenderOptions,
+) -> anyhow::Result<()> {
+    let mut image = Image::new(decoder.ihdr.width, decoder.ihdr.height);
+    let (mut buffer, new_width, new_height) = render_image(&image, options);
+
+    let mut window = Window::new(
+        "Image Display",
+        new_width,
+        new_height,
+        WindowOptions::default(),
+    )?;
+    window.update_with_buffer(&buffer, new_width, new_height)?;
+
+    let start_time = Instant::now();
+    let mut current_pass = None;
+    let mut closed = false;
+    let mut rows = 0usize;
+    let result = loop {
+        let row = match decoder.next_row() {
+            Ok(Some(row)) => row,
+            Ok(None) => break Ok(()),
+            Err(err) => break Err(err),
+        };
+        if row.pass != current_pass {
+            current_pass = row.pass;
+            (buffer, _, _) = render_image(&image, options);
+            window.update_with_buffer(&buffer, new_width, new_height)?;
+        } else if rows.is_multiple_of(EVENT_POLL_ROWS) {
+            window.update();
+        }
+        if !window.is_open() || closed_by_user(&window, options) {
+            closed = true;
+            break Ok(());
+        }
+        fill_row(&mut image, &row);
+        rows += 1;
+    };
+    (buffer, _, _) = render_image(&image, options);
+
+    while !closed && window.is_open() && !closed_by_user(&window, options) {
+        window.update_with_buffer(&buffer, new_width, new_height)?;
+        if timed_out(start_time, timeout) {
+            break;
+        }
+    }
+    Ok(result?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frames(delays_ms: &[u64]) -> Vec<Frame> {
+        delays_ms
+            .iter()
+            .map(|&delay| Frame {
+                image: Image::new(1, 1),
+                delay: Duration::from_millis(delay),
+            })
+            .collect()
+    }
+
+    fn render_options() -> RenderOptions {
+        RenderOptions {
+            scale: 1.0,
+            pixel_aspect_ratio: 1.0,
+            background: None,
+            gama: None,
+            chromaticities: None,
+            srgb: None,
+            icc: None,
+            orientation: None,
+            stereo: None,
+            wait_for_input: false,
+        }
+    }
+
+    #[test]
+    fn srgb_overrides_gama_and_chromaticities() {
+        let mut options = render_options();
+        options.gama = Some(Gama(1.0));
+        options.chromaticities = Some(Chromaticities {
+            red: (0.7, 0.3),
+            ..Chromaticities::SRGB
+        });
+        let transform = ColorTransform::new(&options);
+        assert!(!transform.identity);
+        assert_ne!(transform.apply((128, 64, 32)), (128, 64, 32));
+
+        options.srgb = Some(Srgb::parse(&[0]).unwrap());
+        let transform = ColorTransform::new(&options);
+        assert!(transform.identity);
+        assert_eq!(transform.apply((128, 64, 32)), (128, 64, 32));
+    }
+
+    #[test]
+    fn animations_outlast_the_still_image_timeout() {
+        let timeout = Some(Duration::from_millis(300));
+        assert_eq!(playback_timeout(&frames(&[0]), 1, timeout), timeout);
+        assert_eq!(
+            playback_timeout(&frames(&[500, 250]), 2, timeout),
+            Some(Duration::from_millis(1500))
+        );
+        assert_eq!(playback_timeout(&frames(&[500, 250]), 0, timeout), None);
+        assert_eq!(playback_timeout(&frames(&[500, 250]), 1, None), None);
+    }
+}
@@ -0,0 +1,193 @@
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, PngError>;
+
+#[derive(Debug, Error)]
+pub enum PngError {
+    #[error("Invalid magic number")]
+    Signature,
+
+    #[error("Unexpected end of data at offset {offset}")]
+    Truncated { offset: usize },
+
+    #[error("Unreadable data after the last chunk at offset {offset}")]
+    TrailingData { offset: usize },
+
+    #[error("Invalid chunk type {chunk_type:?} at offset {offset}")]
+    InvalidChunkType { chunk_type: Vec<u8>, offset: usize },
+
+    #[error("Invalid crc in chunk {chunk_type:?} at offset {offset}")]
+    Crc { chunk_type: String, offset: usize },
+
+    #[error("Chunk {chunk_type:?} at offset {offset}: {reason}")]
+    ChunkOrder {
+        chunk_type: String,
+        offset: usize,
+        reason: String,
+    },
+
+    #[error("Unknown critical chunk {chunk_type:?} at offset {offset}")]
+    UnknownCritical { chunk_type: String, offset: usize },
+
+    #[error("No {0} chunk")]
+    MissingChunk(&'static str),
+
+    #[error("Invalid {chunk_type} at offset {offset}: {reason}")]
+    Ihdr {
+        chunk_type: String,
+        offset: usize,
+        reason: String,
+    },
+
+    #[error("Failed decompressing {chunk_type:?} at offset {offset}: {reason}")]
+    Decompression {
+        chunk_type: String,
+        offset: usize,
+        reason: String,
+    },
+
+    /// `offset` is the one of the IDAT chunk the scanline was read from, or of the fcTL chunk
+    /// of an APNG frame
+    #[error("Invalid filter type {filter_type} in the image data at offset {offset}")]
+    Filter { filter_type: u8, offset: usize },
+
+    #[error("Invalid palette in {chunk_type:?} at offset {offset}: {reason}")]
+    Palette {
+        chunk_type: String,
+        offset: usize,
+        reason: String,
+    },
+
+    #[error("Invalid {chunk_type:?} chunk at offset {offset}: {reason}")]
+    Ancillary {
+        chunk_type: String,
+        offset: usize,
+        reason: String,
+    },
+
+    #[error("Invalid animation chunk {chunk_type:?} at offset {offset}: {reason}")]
+    Animation {
+        chunk_type: String,
+        offset: usize,
+        reason: String,
+    },
+
+    #[error("Decode limit exceeded: {limit} {value} is over the maximum of {max}")]
+    LimitExceeded {
+        limit: &'static str,
+        value: u64,
+        max: u64,
+    },
+
+    /// `offset` is the one of the IDAT chunk the data was read from, or of the fcTL chunk of an
+    /// APNG frame
+    #[error("Invalid image data at offset {offset}: {reason}")]
+    ImageData { offset: usize, reason: String },
+
+    #[error("Invalid image: {0}")]
+    InvalidImage(String),
+
+    #[error("ICC profile: {0}")]
+    IccProfile(String),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+impl PngError {
+    pub(crate) fn ancillary(chunk_type: &str, reason: impl Into<String>) -> Self {
+        Self::Ancillary {
+            chunk_type: chunk_type.to_string(),
+            offset: 0,
+            reason: reason.into(),
+        }
+    }
+
+    pub(crate) fn decompression(chunk_type: &str, reason: impl Into<String>) -> Self {
+        Self::Decompression {
+            chunk_type: chunk_type.to_string(),
+            offset: 0,
+            reason: reason.into(),
+        }
+    }
+
+    pub(crate) fn animation(chunk_type: &str, reason: impl Into<String>) -> Self {
+        Self::Animation {
+            chunk_type: chunk_type.to_string(),
+            offset: 0,
+            reason: reason.into(),
+        }
+    }
+
+    pub(crate) fn ihdr(reason: impl Into<String>) -> Self {
+        Self::Ihdr {
+            chunk_type: crate::ihdr::IHDR.to_string(),
+            offset: 0,
+            reason: reason.into(),
+        }
+    }
+
+    pub(crate) fn image_data(reason: impl Into<String>) -> Self {
+        Self::ImageData {
+            offset: 0,
+            reason: reason.into(),
+        }
+    }
+
+    /// Sets the byte offset of errors raised by parsers that only see the chunk content
+    pub(crate) fn at_offset(mut self, chunk_offset: usize) -> Self {
+        match &mut self {
+            Self::Ancillary { offset, .. }
+            | Self::Animation { offset, .. }
+            | Self::Decompression { offset, .. }
+            | Self::ChunkOrder { offset, .. }
+            | Self::Ihdr { offset, .. }
+            | Self::Palette { offset, .. }
+            | Self::Filter { offset, .. }
+            | Self::ImageData { offset, .. } => *offset = chunk_offset,
+            _ => {}
+        }
+        self
+    }
+
+    /// The type of the chunk the error occurred in, if known
+    pub fn chunk_type(&self) -> Option<&str> {
+        match self {
+            Self::Crc { chunk_type, .. }
+            | Self::UnknownCritical { chunk_type, .. }
+            | Self::ChunkOrder { chunk_type, .. }
+            | Self::Decompression { chunk_type, .. }
+            | Self::Ancillary { chunk_type, .. }
+            | Self::Animation { chunk_type, .. }
+            | Self::Ihdr { chunk_type, .. }
+            | Self::Palette { chunk_type, .. } => Some(chunk_type),
+            _ => None,
+        }
+    }
+
+    /// Byte offset in the file of the chunk the error occurred in, if known
+    pub fn offset(&self) -> Option<usize> {
+        match self {
+            Self::Truncated { offset }
+            | Self::TrailingData { offset }
+            | Self::InvalidChunkType { offset, .. }
+            | Self::Crc { offset, .. }
+            | Self::UnknownCritical { offset, .. }
+            | Self::ChunkOrder { offset, .. }
+            | Self::Decompression { offset, .. }
+            | Self::Ancillary { offset, .. }
+            | Self::Animation { offset, .. }
+            | Self::Ihdr { offset, .. }
+            | Self::Palette { offset, .. }
+            | Self::Filter { offset, .. }
+            | Self::ImageData { offset, .. } => Some(*offset),
+            _ => None,
+        }
+    }
+}
+
+impl From<bitreader::BitReaderError> for PngError {
+    fn from(err: bitreader::BitReaderError) -> Self {
+        Self::image_data(err.to_string())
+    }
+}
@@ -1,3 +1,5 @@
+use crate::error::{PngError, Result};
+
 #[derive(Debug)]
 enum PngFilterType {
     None,
@@ -27,14 +29,19 @@ pub fn decode_scanline(
     previous_scanline: &[u8],
     bytes_per_pixel: usize,
     decoded_scanline: &mut [u8],
-) -> anyhow::Result<()> {
+) -> Result<()> {
     let filter_type = match filtered_scanline[0] {
         0 => PngFilterType::None,
         1 => PngFilterType::Sub,
         2 => PngFilterType::Up,
         3 => PngFilterType::Average,
         4 => PngFilterType::Paeth,
-        _ => anyhow::bail!("Invalid filter! {:?}", filtered_scanline[0]),
+        filter_type => {
+            return Err(PngError::Filter {
+                filter_type,
+                offset: 0,
+            })
+        }
     };
     if !matches!(filter_type, PngFilterType::None) {
         // println!("{:?}", filter_type);
@@ -98,3 +105,71 @@ pub fn decode_scanline(
     };
     Ok(())
 }
+
+fn encode_with_filter(
+    filter_type: &PngFilterType,
+    scanline: &[u8],
+    previous_scanline: &[u8],
+    bytes_per_pixel: usize,
+    filtered_scanline: &mut [u8],
+) {
+    for i in 0..scanline.len() {
+        let left = if i >= bytes_per_pixel {
+            scanline[i - bytes_per_pixel]
+        } else {
+            0
+        };
+        let above = previous_scanline[i];
+        let top_left = if i >= bytes_per_pixel {
+            previous_scanline[i - bytes_per_pixel]
+        } else {
+            0
+        };
+        let predicted = match filter_type {
+            PngFilterType::None => 0,
+            PngFilterType::Sub => left,
+            PngFilterType::Up => above,
+            PngFilterType::Average => ((left as u16 + above as u16) / 2) as u8,
+            PngFilterType::Paeth => paeth_predictor(left, above, top_left),
+        };
+        filtered_scanline[i] = scanline[i].wrapping_sub(predicted);
+    }
+}
+
+/// Filters a scanline with the filter type that gives the minimum sum of absolute differences,
+/// writing the filter type byte followed by the filtered bytes into `filtered_scanline`
+pub fn encode_scanline(
+    scanline: &[u8],
+    previous_scanline: &[u8],
+    bytes_per_pixel: usize,
+    filtered_scanline: &mut [u8],
+) {
+    let filters = [
+        PngFilterType::None,
+        PngFilterType::Sub,
+        PngFilterType::Up,
+        PngFilterType::Average,
+        PngFilterType::Paeth,
+    ];
+
+    let mut candidate = vec![0; scanline.len()];
+    let mut best_score = u64::MAX;
+    for (filter_byte, filter_type) in filters.iter().enumerate() {
+        encode_with_filter(
+            filter_type,
+            scanline,
+            previous_scanline,
+            bytes_per_pixel,
+            &mut candidate,
+        );
+        let score = candidate
+            .iter()
+            .map(|&b| (b as i8).unsigned_abs() as u64)
+            .sum();
+        if score < best_score {
+            best_score = score;
+            filtered_scanline[0] = filter_byte as u8;
+            filtered_scanline[1..].copy_from_slice(&candidate);
+        }
+    }
+}
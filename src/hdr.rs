@@ -0,0 +1,173 @@
+use crate::ancillary_chunks::cicp::{CodingIndependentCodePoints, HdrTransfer};
+use crate::ancillary_chunks::clli::ContentLightLevel;
+use crate::ancillary_chunks::mdcv::MasteringDisplayColorVolume;
+use crate::color::{self, Matrix3};
+use crate::png_parser::{Image, Image16};
+
+/// Luminance of SDR white in cd/m², the HDR reference white of ITU-R BT.2408
+const SDR_WHITE: f32 = 203.0;
+
+/// Peak luminance assumed when neither cLLI nor mDCV tell, in cd/m²
+const DEFAULT_PEAK: f32 = 1000.0;
+
+/// Luminance coefficients of BT.2020, the primaries of most HDR content
+const BT2020_LUMINANCE: [f32; 3] = [0.2627, 0.678, 0.0593];
+
+/// Black and white levels of narrow range video, normalized from 8 bits
+const NARROW_BLACK: f32 = 16.0 / 255.0;
+const NARROW_WHITE: f32 = 235.0 / 255.0;
+
+/// Expands a narrow range sample to the full [0, 1] range
+fn expand_narrow_range(encoded: f32) -> f32 {
+    (encoded - NARROW_BLACK) / (NARROW_WHITE - NARROW_BLACK)
+}
+
+/// The PQ EOTF, from an encoded value to cd/m²
+fn pq_to_nits(encoded: f32) -> f32 {
+    const M1: f32 = 2610.0 / 16384.0;
+    const M2: f32 = 2523.0 / 4096.0 * 128.0;
+    const C1: f32 = 3424.0 / 4096.0;
+    const C2: f32 = 2413.0 / 4096.0 * 32.0;
+    const C3: f32 = 2392.0 / 4096.0 * 32.0;
+    let e = encoded.clamp(0.0, 1.0).powf(1.0 / M2);
+    10000.0 * ((e - C1).max(0.0) / (C2 - C3 * e)).powf(1.0 / M1)
+}
+
+/// The inverse HLG OETF, from an encoded value to relative scene light in [0, 1]
+fn hlg_to_scene(encoded: f32) -> f32 {
+    const A: f32 = 0.178_832_77;
+    const B: f32 = 0.284_668_92;
+    const C: f32 = 0.559_910_7;
+    let encoded = encoded.clamp(0.0, 1.0);
+    if encoded <= 0.5 {
+        encoded * encoded / 3.0
+    } else {
+        (((encoded - C) / A).exp() + B) / 12.0
+    }
+}
+
+/// Maps PQ or HLG encoded HDR content to SDR sRGB
+#[derive(Debug, Clone)]
+pub struct ToneMapper {
+    pub transfer: HdrTransfer,
+    /// Brightest luminance of the content in cd/m², mapped to SDR white
+    pub peak_luminance: f32,
+    /// Linear RGB in the cICP primaries to linear sRGB, `None` for BT.709 primaries
+    pub to_srgb: Option<Matrix3>,
+    /// Narrow range samples are expanded before the transfer function
+    pub full_range: bool,
+}
+
+impl ToneMapper {
+    /// `None` unless the cICP transfer function is PQ or HLG
+    pub fn new(
+        cicp: &CodingIndependentCodePoints,
+        mdcv: Option<&MasteringDisplayColorVolume>,
+        clli: Option<&ContentLightLevel>,
+    ) -> Option<Self> {
+        let transfer = cicp.hdr_transfer()?;
+        // HLG is display referred to a 1000 cd/m² display
+        let peak_luminance = match transfer {
+            HdrTransfer::Hlg => DEFAULT_PEAK,
+            HdrTransfer::Pq => clli
+                .map(|clli| clli.max_cll)
+                .filter(|&peak| peak > 0.0)
+                .or_else(|| mdcv.map(|mdcv| mdcv.max_luminance))
+                .filter(|&peak| peak > 0.0)
+                .unwrap_or(DEFAULT_PEAK),
+        };
+        let to_srgb = match cicp.color_primaries {
+            1 => None,
+            _ => cicp.primaries().and_then(|primaries| primaries.to_srgb()),
+        };
+        Some(Self {
+            transfer,
+            peak_luminance,
+            to_srgb,
+            full_range: cicp.full_range,
+        })
+    }
+
+    /// Light of an encoded pixel in cd/m²
+    fn to_nits(&self, encoded: [f32; 3]) -> [f32; 3] {
+        match self.transfer {
+            HdrTransfer::Pq => encoded.map(pq_to_nits),
+            HdrTransfer::Hlg => {
+                // BT.2100 OOTF with the system gamma of a 1000 cd/m² display
+                let scene = encoded.map(hlg_to_scene);
+                let luminance: f32 = (0..3).map(|i| scene[i] * BT2020_LUMINANCE[i]).sum();
+                scene.map(|v| DEFAULT_PEAK * luminance.powf(0.2) * v)
+            }
+        }
+    }
+
+    /// Converts encoded samples in [0, 1] to linear sRGB in [0, 1]
+    pub fn map(&self, encoded: [f32; 3]) -> [f32; 3] {
+        let encoded = if self.full_range {
+            encoded
+        } else {
+            encoded.map(expand_narrow_range)
+        };
+        let relative = self.to_nits(encoded).map(|v| v / SDR_WHITE);
+        // Extended Reinhard on the luminance, keeping the ratios of the channels
+        let white = (self.peak_luminance / SDR_WHITE).max(1.0);
+        let luminance: f32 = (0..3).map(|i| relative[i] * BT2020_LUMINANCE[i]).sum();
+        let mapped = luminance * (1.0 + luminance / (white * white)) / (1.0 + luminance);
+        let scale = if luminance > 0.0 {
+            mapped / luminance
+        } else {
+            0.0
+        };
+        let linear = relative.map(|v| v * scale);
+        let linear = match &self.to_srgb {
+            Some(to_srgb) => color::apply(to_srgb, linear),
+            None => linear,
+        };
+        linear.map(|v| v.clamp(0.0, 1.0))
+    }
+
+    /// Tone maps the image into an 8 bit sRGB image
+    pub fn tone_map(&self, image: &Image16) -> Image {
+        let mut mapped = Image::new(image.width(), image.height());
+        for (pixel, source) in mapped
+            .samples_mut()
+            .chunks_exact_mut(4)
+            .zip(image.samples().chunks_exact(4))
+        {
+            let encoded = [0, 1, 2].map(|i| source[i] as f32 / u16::MAX as f32);
+            let [r, g, b] = self
+                .map(encoded)
+                .map(|v| (color::srgb_encode(v) * 255.0).round() as u8);
+            pixel.copy_from_slice(&[r, g, b, (source[3] >> 8) as u8]);
+        }
+        mapped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pq_mapper(full_range: bool) -> ToneMapper {
+        let cicp = CodingIndependentCodePoints {
+            color_primaries: 1,
+            transfer_function: 16,
+            matrix_coefficients: 0,
+            full_range,
+        };
+        ToneMapper::new(&cicp, None, None).unwrap()
+    }
+
+    #[test]
+    fn narrow_range_is_expanded() {
+        let full = pq_mapper(true);
+        let narrow = pq_mapper(false);
+        assert_eq!(narrow.map([NARROW_BLACK; 3]), [0.0; 3]);
+        assert_eq!(narrow.map([NARROW_WHITE; 3]), full.map([1.0; 3]));
+        let mid = 0.5;
+        let narrow_mid = NARROW_BLACK + mid * (NARROW_WHITE - NARROW_BLACK);
+        for (a, b) in narrow.map([narrow_mid; 3]).iter().zip(full.map([mid; 3])) {
+            assert!((a - b).abs() < 1e-4);
+        }
+    }
+}
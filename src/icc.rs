@@ -0,0 +1,389 @@
+use std::fmt;
+
+use nom::{
+    bytes::complete::take,
+    multi::count,
+    number::complete::{be_i32, be_u16, be_u32, be_u64, u8},
+    IResult,
+};
+
+use crate::color::{self, Matrix3};
+use crate::error::{PngError, Result};
+use crate::png_parser::Image;
+
+const HEADER_LEN: usize = 128;
+
+/// The fixed size header at the start of every ICC profile
+#[derive(Debug, Clone, PartialEq)]
+pub struct IccHeader {
+    pub size: u32,
+    pub cmm_type: String,
+    /// Major, minor and bugfix version
+    pub version: (u8, u8, u8),
+    /// e.g. "mntr" for display profiles, "prtr" for output profiles
+    pub device_class: String,
+    /// Color space of the data the profile applies to, e.g. "RGB " or "GRAY"
+    pub color_space: String,
+    /// Profile connection space, "XYZ " or "Lab "
+    pub pcs: String,
+    /// Year, month, day, hours, minutes, seconds
+    pub created: (u16, u16, u16, u16, u16, u16),
+    pub platform: String,
+    pub flags: u32,
+    pub manufacturer: String,
+    pub model: u32,
+    pub attributes: u64,
+    pub rendering_intent: u32,
+    /// XYZ of the illuminant of the profile connection space, normally D50
+    pub illuminant: [f32; 3],
+    pub creator: String,
+    pub profile_id: [u8; 16],
+}
+
+/// An entry of the tag table
+#[derive(Debug, Clone, PartialEq)]
+pub struct IccTag {
+    pub signature: String,
+    pub offset: u32,
+    pub size: u32,
+}
+
+/// A parsed ICC profile, tag data is read on demand from the profile bytes
+#[derive(Debug, Clone)]
+pub struct IccProfile<'a> {
+    pub header: IccHeader,
+    pub tags: Vec<IccTag>,
+    data: &'a [u8],
+}
+
+fn signature(input: &[u8]) -> IResult<&[u8], String> {
+    let (input, bytes) = take(4usize)(input)?;
+    Ok((input, String::from_utf8_lossy(bytes).into_owned()))
+}
+
+fn s15_fixed16(input: &[u8]) -> IResult<&[u8], f32> {
+    let (input, value) = be_i32(input)?;
+    Ok((input, value as f32 / 65536.0))
+}
+
+fn xyz(input: &[u8]) -> IResult<&[u8], [f32; 3]> {
+    let (input, x) = s15_fixed16(input)?;
+    let (input, y) = s15_fixed16(input)?;
+    let (input, z) = s15_fixed16(input)?;
+    Ok((input, [x, y, z]))
+}
+
+fn parse_header(input: &[u8]) -> IResult<&[u8], IccHeader> {
+    let (input, size) = be_u32(input)?;
+    let (input, cmm_type) = signature(input)?;
+    let (input, major) = u8(input)?;
+    let (input, minor_bugfix) = u8(input)?;
+    let (input, _) = take(2usize)(input)?;
+    let (input, device_class) = signature(input)?;
+    let (input, color_space) = signature(input)?;
+    let (input, pcs) = signature(input)?;
+    let (input, date) = count(be_u16, 6)(input)?;
+    let (input, _) = nom::bytes::complete::tag(b"acsp")(input)?;
+    let (input, platform) = signature(input)?;
+    let (input, flags) = be_u32(input)?;
+    let (input, manufacturer) = signature(input)?;
+    let (input, model) = be_u32(input)?;
+    let (input, attributes) = be_u64(input)?;
+    let (input, rendering_intent) = be_u32(input)?;
+    let (input, illuminant) = xyz(input)?;
+    let (input, creator) = signature(input)?;
+    let (input, profile_id) = take(16usize)(input)?;
+    let (input, _) = take(28usize)(input)?;
+    Ok((
+        input,
+        IccHeader {
+            size,
+            cmm_type,
+            version: (major, minor_bugfix >> 4, minor_bugfix & 0xf),
+            device_class,
+            color_space,
+            pcs,
+            created: (date[0], date[1], date[2], date[3], date[4], date[5]),
+            platform,
+            flags,
+            manufacturer,
+            model,
+            attributes,
+            rendering_intent,
+            illuminant,
+            creator,
+            profile_id: profile_id.try_into().expect("profile id is 16 bytes"),
+        },
+    ))
+}
+
+fn parse_tag_table(input: &[u8]) -> IResult<&[u8], Vec<IccTag>> {
+    let (input, tag_count) = be_u32(input)?;
+    let mut tags = Vec::new();
+    let mut input = input;
+    for _ in 0..tag_count {
+        let (rest, signature) = signature(input)?;
+        let (rest, offset) = be_u32(rest)?;
+        let (rest, size) = be_u32(rest)?;
+        tags.push(IccTag {
+            signature,
+            offset,
+            size,
+        });
+        input = rest;
+    }
+    Ok((input, tags))
+}
+
+impl<'a> IccProfile<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self> {
+        if data.len() < HEADER_LEN {
+            return Err(PngError::IccProfile("Shorter than its header".to_string()));
+        }
+        let (rest, header) =
+            parse_header(data).map_err(|_| PngError::IccProfile("Invalid header".to_string()))?;
+        if header.size as usize != data.len() {
+            return Err(PngError::IccProfile(format!(
+                "Size {} doesn't match its {} bytes",
+                header.size,
+                data.len()
+            )));
+        }
+        let (_, tags) = parse_tag_table(rest)
+            .map_err(|_| PngError::IccProfile("Invalid tag table".to_string()))?;
+        for tag in &tags {
+            if tag.offset as u64 + tag.size as u64 > data.len() as u64 {
+                return Err(PngError::IccProfile(format!(
+                    "Tag {:?} exceeds the profile",
+                    tag.signature
+                )));
+            }
+        }
+        Ok(Self { header, tags, data })
+    }
+
+    /// The content of the tag with the given signature, e.g. "rXYZ"
+    pub fn tag_data(&self, signature: &str) -> Option<&'a [u8]> {
+        self.tags
+            .iter()
+            .find(|tag| tag.signature == signature)
+            .map(|tag| &self.data[tag.offset as usize..(tag.offset + tag.size) as usize])
+    }
+
+    fn xyz_tag(&self, signature: &str) -> Result<[f32; 3]> {
+        fn parse_nom(input: &[u8]) -> IResult<&[u8], [f32; 3]> {
+            let (input, _) = nom::bytes::complete::tag(b"XYZ ")(input)?;
+            let (input, _) = take(4usize)(input)?;
+            xyz(input)
+        }
+        let data = self
+            .tag_data(signature)
+            .ok_or_else(|| PngError::IccProfile(format!("No {} tag", signature)))?;
+        parse_nom(data)
+            .map(|(_, xyz)| xyz)
+            .map_err(|_| PngError::IccProfile(format!("Invalid {} tag", signature)))
+    }
+
+    fn curve_tag(&self, signature: &str) -> Result<ToneCurve> {
+        let data = self
+            .tag_data(signature)
+            .ok_or_else(|| PngError::IccProfile(format!("No {} tag", signature)))?;
+        ToneCurve::parse(data)
+            .ok_or_else(|| PngError::IccProfile(format!("Invalid {} tag", signature)))
+    }
+}
+
+/// A tone reproduction curve, mapping encoded values in [0, 1] to linear ones
+#[derive(Debug, Clone, PartialEq)]
+pub enum ToneCurve {
+    Identity,
+    Gamma(f32),
+    /// Evenly spaced samples, linearly interpolated
+    Table(Vec<f32>),
+    /// ICC parametric function type 0 to 4 with its parameters (g, a, b, c, d, e, f)
+    Parametric {
+        function_type: u16,
+        params: [f32; 7],
+    },
+}
+
+impl ToneCurve {
+    fn parse(input: &[u8]) -> Option<Self> {
+        fn curv(input: &[u8]) -> IResult<&[u8], ToneCurve> {
+            let (input, entries) = be_u32(input)?;
+            let (input, table) = count(be_u16, entries as usize)(input)?;
+            let curve = match table[..] {
+                [] => ToneCurve::Identity,
+                [gamma] => ToneCurve::Gamma(gamma as f32 / 256.0),
+                _ => ToneCurve::Table(table.iter().map(|&v| v as f32 / 65535.0).collect()),
+            };
+            Ok((input, curve))
+        }
+        fn para(input: &[u8]) -> IResult<&[u8], ToneCurve> {
+            let (input, function_type) = be_u16(input)?;
+            let (input, _) = take(2usize)(input)?;
+            let param_count = match function_type {
+                0 => 1,
+                1 => 3,
+                2 => 4,
+                3 => 5,
+                4 => 7,
+                _ => {
+                    return Err(nom::Err::Error(nom::error::Error::new(
+                        input,
+                        nom::error::ErrorKind::Verify,
+                    )))
+                }
+            };
+            let (input, values) = count(s15_fixed16, param_count)(input)?;
+            let mut params = [0.0; 7];
+            params[..param_count].copy_from_slice(&values);
+            Ok((
+                input,
+                ToneCurve::Parametric {
+                    function_type,
+                    params,
+                },
+            ))
+        }
+
+        let (type_signature, body) = (input.get(..4)?, input.get(8..)?);
+        let (_, curve) = match type_signature {
+            b"curv" => curv(body).ok()?,
+            b"para" => para(body).ok()?,
+            _ => return None,
+        };
+        Some(curve)
+    }
+
+    pub fn eval(&self, x: f32) -> f32 {
+        let x = x.clamp(0.0, 1.0);
+        match self {
+            ToneCurve::Identity => x,
+            ToneCurve::Gamma(gamma) => x.powf(*gamma),
+            ToneCurve::Table(table) => {
+                let position = x * (table.len() - 1) as f32;
+                let i = (position.floor() as usize).min(table.len() - 2);
+                let t = position - i as f32;
+                table[i] * (1.0 - t) + table[i + 1] * t
+            }
+            ToneCurve::Parametric {
+                function_type,
+                params: [g, a, b, c, d, e, f],
+            } => match function_type {
+                0 => x.powf(*g),
+                1 if x >= -b / a => (a * x + b).powf(*g),
+                1 => 0.0,
+                2 if x >= -b / a => (a * x + b).powf(*g) + c,
+                2 => *c,
+                3 if x >= *d => (a * x + b).powf(*g),
+                3 => c * x,
+                _ if x >= *d => (a * x + b).powf(*g) + e,
+                _ => c * x + f,
+            },
+        }
+    }
+}
+
+/// Converts samples described by a matrix/TRC RGB or a gray TRC profile to sRGB
+#[derive(Debug, Clone)]
+pub struct IccTransform {
+    /// Red, green and blue curves, the gray curve three times for gray profiles
+    pub curves: [ToneCurve; 3],
+    /// Linear RGB to linear sRGB, `None` for gray profiles
+    pub to_srgb: Option<Matrix3>,
+}
+
+impl IccTransform {
+    pub fn new(profile: &IccProfile) -> Result<Self> {
+        if profile.header.pcs != "XYZ " {
+            return Err(PngError::IccProfile(format!(
+                "Unsupported connection space {:?}",
+                profile.header.pcs
+            )));
+        }
+        match profile.header.color_space.as_str() {
+            "RGB " => {
+                let [r, g, b] = ["rXYZ", "gXYZ", "bXYZ"].map(|tag| profile.xyz_tag(tag));
+                let (r, g, b) = (r?, g?, b?);
+                let to_pcs = [[r[0], g[0], b[0]], [r[1], g[1], b[1]], [r[2], g[2], b[2]]];
+                let to_srgb =
+                    color::multiply(&color::xyz_to_srgb(profile.header.illuminant), &to_pcs);
+                Ok(Self {
+                    curves: [
+                        profile.curve_tag("rTRC")?,
+                        profile.curve_tag("gTRC")?,
+                        profile.curve_tag("bTRC")?,
+                    ],
+                    to_srgb: Some(to_srgb),
+                })
+            }
+            "GRAY" => {
+                let curve = profile.curve_tag("kTRC")?;
+                Ok(Self {
+                    curves: [curve.clone(), curve.clone(), curve],
+                    to_srgb: None,
+                })
+            }
+            color_space => Err(PngError::IccProfile(format!(
+                "Unsupported color space {:?}",
+                color_space
+            ))),
+        }
+    }
+
+    /// Converts encoded samples in [0, 1] to linear sRGB
+    pub fn to_linear_srgb(&self, rgb: [f32; 3]) -> [f32; 3] {
+        let linear = [0, 1, 2].map(|i| self.curves[i].eval(rgb[i]));
+        match &self.to_srgb {
+            Some(to_srgb) => color::apply(to_srgb, linear),
+            None => linear,
+        }
+    }
+
+    /// Converts every pixel of the image to sRGB in place
+    pub fn convert_to_srgb(&self, image: &mut Image) {
+        let luts = [0, 1, 2].map(|i| {
+            let curve = &self.curves[i];
+            (0..256)
+                .map(|v| curve.eval(v as f32 / 255.0))
+                .collect::<Vec<_>>()
+        });
+        for pixel in image.samples_mut().chunks_exact_mut(4) {
+            let linear = [0, 1, 2].map(|i| luts[i][pixel[i] as usize]);
+            let linear = match &self.to_srgb {
+                Some(to_srgb) => color::apply(to_srgb, linear),
+                None => linear,
+            };
+            for (sample, v) in pixel.iter_mut().zip(linear) {
+                *sample = (color::srgb_encode(v.clamp(0.0, 1.0)) * 255.0).round() as u8;
+            }
+        }
+    }
+}
+
+impl fmt::Display for IccProfile<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let header = &self.header;
+        let (major, minor, bugfix) = header.version;
+        writeln!(f, "Version: {}.{}.{}", major, minor, bugfix)?;
+        writeln!(f, "Device class: {}", header.device_class)?;
+        writeln!(f, "Color space: {}", header.color_space)?;
+        writeln!(f, "Connection space: {}", header.pcs)?;
+        let (year, month, day, hours, minutes, seconds) = header.created;
+        writeln!(
+            f,
+            "Created: {:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            year, month, day, hours, minutes, seconds
+        )?;
+        write!(
+            f,
+            "Tags: {}",
+            self.tags
+                .iter()
+                .map(|tag| tag.signature.trim_end())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+}
@@ -1,11 +1,18 @@
-use anyhow::Context;
+use std::fmt;
+
 use nom::number::complete::{be_u32, u8};
 use nom::IResult;
 
 use crate::color_type::ColorType;
+use crate::error::{PngError, Result};
+use crate::limits::DecodeLimits;
 use crate::plte::Palette;
+use crate::png_parser::ADAM7_PASSES;
+
+pub const IHDR: &str = "IHDR";
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct IhdrChunk {
     pub width: u32,
     pub height: u32,
@@ -17,6 +24,7 @@ pub struct IhdrChunk {
 }
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum FilterMethod {
     FiveFilter,
 }
@@ -28,9 +36,15 @@ impl FilterMethod {
             _ => None,
         }
     }
+    pub fn to_u8(self) -> u8 {
+        match self {
+            Self::FiveFilter => 0,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum CompressionMethod {
     Zlib,
 }
@@ -41,9 +55,15 @@ impl CompressionMethod {
             _ => None,
         }
     }
+    pub fn to_u8(self) -> u8 {
+        match self {
+            Self::Zlib => 0,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum InterlaceMethod {
     Adam7,
     None,
@@ -56,14 +76,105 @@ impl InterlaceMethod {
             _ => None,
         }
     }
+    pub fn to_u8(self) -> u8 {
+        match self {
+            Self::None => 0,
+            Self::Adam7 => 1,
+        }
+    }
 }
 
+impl IhdrChunk {
+    pub fn bits_per_pixel(&self) -> usize {
+        self.bit_depth as usize * self.color_type.values_per_pixel() as usize
+    }
+
+    /// Bit depth of the samples, the palette entries of indexed images are always 8 bits
+    pub fn sample_depth(&self) -> u8 {
+        match self.color_type {
+            ColorType::Palette(_) => 8,
+            _ => self.bit_depth,
+        }
+    }
+
+    /// Bytes per complete pixel rounded up to one, the distance used by the filters
+    pub fn bytes_per_pixel(&self) -> usize {
+        (self.bit_depth.div_ceil(8) * self.color_type.values_per_pixel()) as usize
+    }
+
+    /// Length in bytes of an unfiltered scanline `width` pixels wide
+    pub fn scanline_len(&self, width: u32) -> usize {
+        (self.bits_per_pixel() * width as usize).div_ceil(8)
+    }
+
+    /// Width and height of every reduced image stored in the image data, in order.
+    /// A non interlaced image is a single pass, empty Adam7 passes are left out.
+    pub fn passes(&self) -> Vec<(u32, u32)> {
+        let (width, height) = (self.width as usize, self.height as usize);
+        match self.interlace_method {
+            InterlaceMethod::None => vec![(self.width, self.height)],
+            InterlaceMethod::Adam7 => ADAM7_PASSES
+                .iter()
+                .filter(|((start_x, start_y), _)| *start_x < width && *start_y < height)
+                .map(|((start_x, start_y), (step_x, step_y))| {
+                    let pass_width = (width - start_x).div_ceil(*step_x);
+                    let pass_height = (height - start_y).div_ceil(*step_y);
+                    (pass_width as u32, pass_height as u32)
+                })
+                .collect(),
+        }
+    }
+
+    /// Length in bytes of the inflated image data, filter type bytes included
+    pub fn image_data_len(&self) -> usize {
+        self.passes()
+            .into_iter()
+            .map(|(width, height)| (1 + self.scanline_len(width)) * height as usize)
+            .sum()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(13);
+        bytes.extend_from_slice(&self.width.to_be_bytes());
+        bytes.extend_from_slice(&self.height.to_be_bytes());
+        bytes.push(self.bit_depth);
+        bytes.push(self.color_type.to_u8());
+        bytes.push(self.compression_method.to_u8());
+        bytes.push(self.filter_method.to_u8());
+        bytes.push(self.interlace_method.to_u8());
+        bytes
+    }
+}
+
+impl fmt::Display for IhdrChunk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Size: {} x {}", self.width, self.height)?;
+        writeln!(f, "Bit depth: {}", self.bit_depth)?;
+        write!(f, "Color type: {}", self.color_type.name())?;
+        if let ColorType::Palette(palette) = &self.color_type {
+            write!(f, " ({} entries)", palette.entries.len())?;
+        }
+        writeln!(f)?;
+        let CompressionMethod::Zlib = self.compression_method;
+        writeln!(f, "Compression: deflate")?;
+        let FilterMethod::FiveFilter = self.filter_method;
+        writeln!(f, "Filter method: adaptive, five filter types")?;
+        let interlace = match self.interlace_method {
+            InterlaceMethod::None => "none",
+            InterlaceMethod::Adam7 => "Adam7",
+        };
+        write!(f, "Interlace: {}", interlace)
+    }
+}
+
+/// `offset` is the byte offset of the IHDR chunk, errors are reported at it
 pub fn parse_ihdr<'a>(
     input: &'a [u8],
-
+    offset: usize,
     plte: Option<Palette>,
     trns_content: Option<&'a [u8]>,
-) -> anyhow::Result<(&'a [u8], IhdrChunk)> {
+    limits: &DecodeLimits,
+) -> Result<(&'a [u8], IhdrChunk)> {
     type IHDRRaw = (u32, u32, u8, u8, u8, u8, u8);
     fn parse_nom(input: &[u8]) -> IResult<&[u8], IHDRRaw> {
         let (input, width) = be_u32(input)?;
@@ -85,30 +196,41 @@ pub fn parse_ihdr<'a>(
         Ok((input, ihdr_values))
     }
 
+    let invalid = |reason: String| PngError::ihdr(reason).at_offset(offset);
+
     let (
         input,
         (width, height, bit_depth, color_type, compression_method, filter_method, interlace_method),
-    ) = parse_nom(input)
-        .map_err(|e| e.to_owned())
-        .context("Failed parsing ihdr")?;
+    ) = parse_nom(input).map_err(|e| invalid(format!("Failed parsing ihdr: {}", e)))?;
+
+    if width == 0 || height == 0 {
+        return Err(invalid(format!("Invalid size: {}x{}", width, height)));
+    }
+    limits.check_size(width, height)?;
 
-    let color_type = ColorType::from_u8(color_type, bit_depth, plte, trns_content)?;
+    let color_type =
+        ColorType::from_u8(color_type, bit_depth, plte, trns_content).map_err(|e| match e {
+            PngError::Ihdr { .. } => e.at_offset(offset),
+            e => e,
+        })?;
     let interlace_method = InterlaceMethod::from_u8(interlace_method)
-        .context(format!("Invalid interlace_method: {}", interlace_method))?;
+        .ok_or_else(|| invalid(format!("Invalid interlace_method: {}", interlace_method)))?;
 
-    let compression_method = CompressionMethod::from_u8(compression_method).context(format!(
-        "Invalid compression_method: {}",
-        compression_method
-    ))?;
+    let compression_method = CompressionMethod::from_u8(compression_method).ok_or_else(|| {
+        invalid(format!(
+            "Invalid compression_method: {}",
+            compression_method
+        ))
+    })?;
 
     let filter_method = FilterMethod::from_u8(filter_method)
-        .context(format!("Invalid filter_method: {}", filter_method))?;
+        .ok_or_else(|| invalid(format!("Invalid filter_method: {}", filter_method)))?;
 
     let CompressionMethod::Zlib = compression_method;
     let FilterMethod::FiveFilter = filter_method;
 
     if !color_type.valid_bit_depths().contains(&bit_depth) {
-        anyhow::bail!("Invalid bit_depth: {}", bit_depth)
+        return Err(invalid(format!("Invalid bit_depth: {}", bit_depth)));
     }
 
     Ok((
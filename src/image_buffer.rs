@@ -0,0 +1,178 @@
+use crate::error::{PngError, Result};
+
+/// Samples per pixel, every pixel is stored as RGBA
+pub const CHANNELS: usize = 4;
+
+/// An RGBA image stored row after row in a single contiguous buffer of samples
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageBuffer<S> {
+    width: u32,
+    height: u32,
+    /// Samples between the start of two consecutive rows
+    stride: usize,
+    samples: Vec<S>,
+}
+
+/// A borrowed rectangle of an [`ImageBuffer`]
+#[derive(Debug, Clone, Copy)]
+pub struct ImageView<'a, S> {
+    width: u32,
+    height: u32,
+    stride: usize,
+    samples: &'a [S],
+}
+
+impl<S: Copy + Default> ImageBuffer<S> {
+    /// Creates an image filled with zeroed (transparent black) pixels
+    pub fn new(width: u32, height: u32) -> Self {
+        let stride = width as usize * CHANNELS;
+        Self {
+            width,
+            height,
+            stride,
+            samples: vec![S::default(); stride * height as usize],
+        }
+    }
+
+    pub fn from_samples(width: u32, height: u32, samples: Vec<S>) -> Result<Self> {
+        let stride = width as usize * CHANNELS;
+        if samples.len() != stride * height as usize {
+            return Err(PngError::InvalidImage(format!(
+                "Sample count {} doesn't match a {}x{} image",
+                samples.len(),
+                width,
+                height
+            )));
+        }
+        Ok(Self {
+            width,
+            height,
+            stride,
+            samples,
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    pub fn samples(&self) -> &[S] {
+        &self.samples
+    }
+
+    pub fn samples_mut(&mut self) -> &mut [S] {
+        &mut self.samples
+    }
+
+    pub fn into_samples(self) -> Vec<S> {
+        self.samples
+    }
+
+    pub fn row(&self, y: u32) -> &[S] {
+        let start = y as usize * self.stride;
+        &self.samples[start..start + self.width as usize * CHANNELS]
+    }
+
+    pub fn row_mut(&mut self, y: u32) -> &mut [S] {
+        let start = y as usize * self.stride;
+        &mut self.samples[start..start + self.width as usize * CHANNELS]
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[S]> {
+        (0..self.height).map(|y| self.row(y))
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> (S, S, S, S) {
+        let i = y as usize * self.stride + x as usize * CHANNELS;
+        let p = &self.samples[i..i + CHANNELS];
+        (p[0], p[1], p[2], p[3])
+    }
+
+    pub fn set_pixel(&mut self, x: u32, y: u32, (r, g, b, a): (S, S, S, S)) {
+        let i = y as usize * self.stride + x as usize * CHANNELS;
+        self.samples[i..i + CHANNELS].copy_from_slice(&[r, g, b, a]);
+    }
+
+    pub fn as_view(&self) -> ImageView<'_, S> {
+        self.view(0, 0, self.width, self.height)
+    }
+
+    /// A zero-copy view of the `width`x`height` rectangle starting at (`x`, `y`)
+    pub fn view(&self, x: u32, y: u32, width: u32, height: u32) -> ImageView<'_, S> {
+        assert!(
+            x + width <= self.width && y + height <= self.height,
+            "View out of the image bounds"
+        );
+        let start = y as usize * self.stride + x as usize * CHANNELS;
+        let end = if height == 0 {
+            start
+        } else {
+            start + (height as usize - 1) * self.stride + width as usize * CHANNELS
+        };
+        ImageView {
+            width,
+            height,
+            stride: self.stride,
+            samples: &self.samples[start..end],
+        }
+    }
+
+    pub fn map<T: Copy + Default>(&self, f: impl Fn(S) -> T) -> ImageBuffer<T> {
+        ImageBuffer {
+            width: self.width,
+            height: self.height,
+            stride: self.stride,
+            samples: self.samples.iter().map(|&s| f(s)).collect(),
+        }
+    }
+}
+
+impl ImageBuffer<u8> {
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.samples
+    }
+}
+
+impl ImageBuffer<u16> {
+    /// Converts to 8 bit samples by dropping the low byte of every sample
+    pub fn to_8bit(&self) -> ImageBuffer<u8> {
+        self.map(|s| (s >> 8) as u8)
+    }
+}
+
+impl<'a, S: Copy> ImageView<'a, S> {
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    pub fn row(&self, y: u32) -> &'a [S] {
+        let start = y as usize * self.stride;
+        &self.samples[start..start + self.width as usize * CHANNELS]
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &'a [S]> + '_ {
+        (0..self.height).map(|y| self.row(y))
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> (S, S, S, S) {
+        let i = y as usize * self.stride + x as usize * CHANNELS;
+        let p = &self.samples[i..i + CHANNELS];
+        (p[0], p[1], p[2], p[3])
+    }
+}
@@ -0,0 +1,384 @@
+use std::fmt;
+
+use nom::{bytes::complete::take, number::complete::be_u32, IResult};
+
+use crate::ancillary_chunks::AncillaryChunks;
+use crate::chunk::{calculate_crc, parse_chunk_type};
+use crate::error::PngError;
+use crate::ihdr::IhdrChunk;
+use crate::png_parser::{DecodeOptions, Png, IDAT, IEND, MAGIC_NUMBER};
+
+const FILTER_TYPES: [&str; 5] = ["None", "Sub", "Up", "Average", "Paeth"];
+
+/// Whether the crc stored after a chunk matches its content
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum CrcStatus {
+    Ok,
+    Mismatch {
+        stored: u32,
+        computed: u32,
+    },
+    /// The file ends before the crc
+    Missing,
+}
+
+impl fmt::Display for CrcStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CrcStatus::Ok => write!(f, "OK"),
+            CrcStatus::Mismatch { stored, computed } => write!(
+                f,
+                "mismatch, stored {:08x}, computed {:08x}",
+                stored, computed
+            ),
+            CrcStatus::Missing => write!(f, "missing"),
+        }
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ChunkInfo<'a> {
+    pub chunk_type: &'a str,
+    /// Byte offset of the start of the chunk in the file
+    pub offset: usize,
+    /// Length of the content as stored, truncated chunks hold less
+    pub length: u32,
+    pub crc: CrcStatus,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub data: &'a [u8],
+}
+
+/// Filter types of the scanlines in the image data
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FilterStats {
+    /// Scanlines of each filter type, indexed by the filter type byte
+    pub counts: [usize; 5],
+    /// Scanlines with a filter type byte over 4
+    pub invalid: usize,
+}
+
+impl FilterStats {
+    fn new(ihdr: &IhdrChunk, data: &[u8]) -> Self {
+        let mut stats = Self::default();
+        let mut remaining = data;
+        for (width, height) in ihdr.passes() {
+            let scanline_len = 1 + ihdr.scanline_len(width);
+            for _ in 0..height {
+                if remaining.len() < scanline_len {
+                    return stats;
+                }
+                match stats.counts.get_mut(remaining[0] as usize) {
+                    Some(count) => *count += 1,
+                    None => stats.invalid += 1,
+                }
+                remaining = &remaining[scanline_len..];
+            }
+        }
+        stats
+    }
+}
+
+/// Structural report of a png file, for triaging damaged files.
+/// Every problem found is collected instead of stopping at the first one.
+#[derive(Debug)]
+pub struct PngInfo<'a> {
+    /// Every chunk up to IEND or the first one that can't be read
+    pub chunks: Vec<ChunkInfo<'a>>,
+    /// `None` when the file couldn't be decoded far enough to read it
+    pub ihdr: Option<IhdrChunk>,
+    /// Empty when the file couldn't be decoded far enough to read them
+    pub ancillary_chunks: AncillaryChunks<'a>,
+    /// Length of the content of all IDAT chunks
+    pub compressed_len: usize,
+    /// Length of the image data inflated from the IDAT chunks
+    pub inflated_len: usize,
+    pub filters: FilterStats,
+    pub errors: Vec<PngError>,
+}
+
+/// Reads chunk headers and crcs without validating anything but the chunk type
+fn scan_chunks(input: &[u8]) -> Vec<ChunkInfo<'_>> {
+    fn parse_header(input: &[u8]) -> IResult<&[u8], (u32, &[u8])> {
+        let (input, length) = be_u32(input)?;
+        let (input, chunk_type) = take(4usize)(input)?;
+        Ok((input, (length, chunk_type)))
+    }
+
+    let mut chunks = Vec::new();
+    let mut remaining_input = &input[MAGIC_NUMBER.len()..];
+    while !remaining_input.is_empty() {
+        let offset = input.len() - remaining_input.len();
+        let Ok((rest, (length, chunk_type))) = parse_header(remaining_input) else {
+            break;
+        };
+        let Ok(chunk_type) = parse_chunk_type(chunk_type, offset) else {
+            break;
+        };
+        let data = &rest[..(length as usize).min(rest.len())];
+        let rest = &rest[data.len()..];
+        let Some(stored) = rest.get(..4) else {
+            chunks.push(ChunkInfo {
+                chunk_type,
+                offset,
+                length,
+                crc: CrcStatus::Missing,
+                data,
+            });
+            break;
+        };
+        let stored = u32::from_be_bytes([stored[0], stored[1], stored[2], stored[3]]);
+        let computed = calculate_crc(chunk_type.as_bytes(), data);
+        let crc = if stored == computed {
+            CrcStatus::Ok
+        } else {
+            CrcStatus::Mismatch { stored, computed }
+        };
+        chunks.push(ChunkInfo {
+            chunk_type,
+            offset,
+            length,
+            crc,
+            data,
+        });
+        remaining_input = &rest[4..];
+        if chunk_type == IEND {
+            break;
+        }
+    }
+    chunks
+}
+
+impl<'a> PngInfo<'a> {
+    pub fn new(input: &'a [u8]) -> Self {
+        if !input.starts_with(&MAGIC_NUMBER) {
+            return Self {
+                chunks: Vec::new(),
+                ihdr: None,
+                ancillary_chunks: AncillaryChunks(Vec::new()),
+                compressed_len: 0,
+                inflated_len: 0,
+                filters: FilterStats::default(),
+                errors: vec![PngError::Signature],
+            };
+        }
+        let chunks = scan_chunks(input);
+
+        // The recovering decoder reports every problem it can get past as a warning
+        let options = DecodeOptions {
+            recover: true,
+            ..Default::default()
+        };
+        let (ihdr, ancillary_chunks, inflated_len, filters, mut errors) =
+            match Png::with_options(input, &options) {
+                Ok(png) => {
+                    // The padding of missing image data isn't counted
+                    let filters = FilterStats::new(&png.ihdr, &png.data[..png.inflated_len]);
+                    (
+                        Some(png.ihdr),
+                        png.other_chunks,
+                        png.inflated_len,
+                        filters,
+                        png.warnings,
+                    )
+                }
+                Err(err) => (
+                    None,
+                    AncillaryChunks(Vec::new()),
+                    0,
+                    FilterStats::default(),
+                    vec![err],
+                ),
+            };
+
+        let compressed_len = chunks
+            .iter()
+            .filter(|chunk| chunk.chunk_type == IDAT)
+            .map(|chunk| chunk.data.len())
+            .sum();
+        if filters.invalid > 0 {
+            let idat_offset = chunks
+                .iter()
+                .find(|chunk| chunk.chunk_type == IDAT)
+                .map_or(0, |chunk| chunk.offset);
+            errors.push(
+                PngError::image_data(format!(
+                    "{} scanlines with an invalid filter type",
+                    filters.invalid
+                ))
+                .at_offset(idat_offset),
+            );
+        }
+
+        Self {
+            chunks,
+            ihdr,
+            ancillary_chunks,
+            compressed_len,
+            inflated_len,
+            filters,
+            errors,
+        }
+    }
+
+    /// Inflated bytes per compressed byte of the image data
+    pub fn compression_ratio(&self) -> Option<f64> {
+        (self.compressed_len > 0).then(|| self.inflated_len as f64 / self.compressed_len as f64)
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+/// Errors are serialized as their messages, along with the verdict
+#[cfg(feature = "serde")]
+impl serde::Serialize for PngInfo<'_> {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let errors: Vec<String> = self.errors.iter().map(PngError::to_string).collect();
+        let mut state = serializer.serialize_struct("PngInfo", 9)?;
+        state.serialize_field("chunks", &self.chunks)?;
+        state.serialize_field("ihdr", &self.ihdr)?;
+        state.serialize_field("ancillary_chunks", &self.ancillary_chunks)?;
+        state.serialize_field("compressed_len", &self.compressed_len)?;
+        state.serialize_field("inflated_len", &self.inflated_len)?;
+        state.serialize_field("compression_ratio", &self.compression_ratio())?;
+        state.serialize_field("filters", &self.filters)?;
+        state.serialize_field("errors", &errors)?;
+        state.serialize_field("ok", &self.is_ok())?;
+        state.end()
+    }
+}
+
+impl fmt::Display for PngInfo<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Chunks:")?;
+        writeln!(f, "  {:>10} {:>10}  type  crc", "offset", "length")?;
+        for chunk in &self.chunks {
+            writeln!(
+                f,
+                "  {:>10} {:>10}  {}  {}",
+                chunk.offset, chunk.length, chunk.chunk_type, chunk.crc
+            )?;
+        }
+
+        if let Some(ihdr) = &self.ihdr {
+            writeln!(f, "\nImage header:")?;
+            for line in ihdr.to_string().lines() {
+                writeln!(f, "  {}", line)?;
+            }
+
+            let idat_count = self.chunks.iter().filter(|c| c.chunk_type == IDAT).count();
+            writeln!(f, "\nImage data:")?;
+            writeln!(
+                f,
+                "  {} IDAT chunks, {} compressed bytes, {} of {} inflated bytes",
+                idat_count,
+                self.compressed_len,
+                self.inflated_len,
+                ihdr.image_data_len()
+            )?;
+            if let Some(ratio) = self.compression_ratio() {
+                writeln!(f, "  Compression ratio: {:.2}:1", ratio)?;
+            }
+
+            let scanlines = self.filters.counts.iter().sum::<usize>() + self.filters.invalid;
+            writeln!(f, "\nFilter types of {} scanlines:", scanlines)?;
+            for (name, count) in FILTER_TYPES.iter().zip(self.filters.counts) {
+                writeln!(f, "  {:<8} {}", name, count)?;
+            }
+            if self.filters.invalid > 0 {
+                writeln!(f, "  {:<8} {}", "Invalid", self.filters.invalid)?;
+            }
+        }
+
+        if !self.errors.is_empty() {
+            writeln!(f, "\nErrors:")?;
+            for error in &self.errors {
+                writeln!(f, "  {}", error)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ihdr::InterlaceMethod;
+    use crate::test_support;
+
+    #[test]
+    fn valid_file_reports_every_chunk() {
+        let png = test_support::rgba_png(4, 3, InterlaceMethod::None);
+        let info = PngInfo::new(&png);
+        assert!(info.is_ok(), "{:?}", info.errors);
+
+        let (first, last) = (&info.chunks[0], info.chunks.last().unwrap());
+        assert_eq!(
+            (first.chunk_type, first.offset, first.length),
+            ("IHDR", 8, 13)
+        );
+        assert_eq!(last.chunk_type, IEND);
+        assert!(info.chunks[1..info.chunks.len() - 1]
+            .iter()
+            .all(|chunk| chunk.chunk_type == IDAT));
+        assert!(info.chunks.iter().all(|chunk| chunk.crc == CrcStatus::Ok));
+
+        // A filter type byte before each scanline of 4 RGBA pixels
+        assert_eq!(info.inflated_len, 3 * (1 + 4 * 4));
+        assert_eq!(info.filters.counts.iter().sum::<usize>(), 3);
+        assert_eq!(info.filters.invalid, 0);
+        assert!(info.compression_ratio().is_some());
+    }
+
+    #[test]
+    fn damaged_files_collect_errors() {
+        let mut png = test_support::rgba_png(4, 3, InterlaceMethod::None);
+        let last = png.len() - 1;
+        png[last] ^= 0xff;
+        let info = PngInfo::new(&png);
+        assert!(matches!(
+            info.chunks.last().unwrap().crc,
+            CrcStatus::Mismatch { .. }
+        ));
+        assert!(!info.is_ok());
+
+        png.truncate(last);
+        let info = PngInfo::new(&png);
+        assert_eq!(info.chunks.last().unwrap().crc, CrcStatus::Missing);
+        assert!(!info.is_ok());
+
+        let info = PngInfo::new(b"GIF89a");
+        assert!(info.chunks.is_empty());
+        assert!(matches!(info.errors[..], [PngError::Signature]));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_report_holds_the_verdict() {
+        let mut png = test_support::rgba_png(4, 3, InterlaceMethod::None);
+        let json = serde_json::to_value(PngInfo::new(&png)).unwrap();
+        assert_eq!(json["chunks"][0]["chunk_type"], "IHDR");
+        assert_eq!(json["chunks"][0]["crc"], "Ok");
+        assert_eq!(json["ihdr"]["width"], 4);
+        assert_eq!(json["ihdr"]["color_type"]["type"], "Rgba");
+        assert_eq!(json["inflated_len"], 3 * (1 + 4 * 4));
+        assert_eq!(json["errors"], serde_json::json!([]));
+        assert_eq!(json["ok"], true);
+
+        let last = png.len() - 1;
+        png[last] ^= 0xff;
+        let json = serde_json::to_value(PngInfo::new(&png)).unwrap();
+        assert!(json["chunks"].as_array().unwrap().last().unwrap()["crc"]["Mismatch"].is_object());
+        assert_eq!(json["ok"], false);
+        assert!(json["errors"][0].is_string());
+    }
+}
@@ -0,0 +1,28 @@
+pub mod ancillary_chunks;
+pub mod apng;
+pub mod chunk;
+pub mod chunk_order;
+pub mod color;
+pub mod color_type;
+#[cfg(feature = "viewer")]
+pub mod draw_image;
+pub mod error;
+pub mod filter_apply;
+pub mod hdr;
+pub mod icc;
+pub mod ihdr;
+pub mod image_buffer;
+pub mod info;
+pub mod limits;
+pub mod plte;
+pub mod png_parser;
+pub mod png_writer;
+pub mod raw_image;
+pub mod run_n;
+pub mod stream_decoder;
+#[cfg(test)]
+mod test_support;
+
+pub use error::PngError;
+pub use image_buffer::{ImageBuffer, ImageView};
+pub use png_parser::{Image, Image16, Pixel, Pixel16, Png};
@@ -0,0 +1,76 @@
+use crate::error::{PngError, Result};
+
+/// Upper bounds on what a file can make the decoder allocate
+#[derive(Debug, Clone)]
+pub struct DecodeLimits {
+    pub max_width: u32,
+    pub max_height: u32,
+    /// Maximum of `width * height`
+    pub max_pixels: u64,
+    /// Maximum length of the inflated data of the image or of a single APNG frame
+    pub max_inflated_bytes: usize,
+    /// Maximum length of the text of a tEXt, zTXt or iTXt chunk, after decompression
+    pub max_text_len: usize,
+    /// Maximum number of chunks in the file
+    pub max_chunks: usize,
+    /// Maximum size in bytes of the decoded pixels, summed over the frames of an animation
+    pub max_decoded_bytes: u64,
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        Self {
+            max_width: 1 << 24,
+            max_height: 1 << 24,
+            max_pixels: 1 << 28,
+            max_inflated_bytes: 1 << 30,
+            max_text_len: 1 << 20,
+            max_chunks: 1 << 16,
+            // Enough for a 16 bit image of `max_pixels`
+            max_decoded_bytes: 1 << 31,
+        }
+    }
+}
+
+impl DecodeLimits {
+    /// Limits that accept anything the format can describe
+    pub fn unlimited() -> Self {
+        Self {
+            max_width: u32::MAX,
+            max_height: u32::MAX,
+            max_pixels: u64::MAX,
+            max_inflated_bytes: usize::MAX,
+            max_text_len: usize::MAX,
+            max_chunks: usize::MAX,
+            max_decoded_bytes: u64::MAX,
+        }
+    }
+
+    pub(crate) fn check_size(&self, width: u32, height: u32) -> Result<()> {
+        check("width", width as u64, self.max_width as u64)?;
+        check("height", height as u64, self.max_height as u64)?;
+        check("pixels", width as u64 * height as u64, self.max_pixels)
+    }
+
+    pub(crate) fn check_text_len(&self, len: usize) -> Result<()> {
+        check("text length", len as u64, self.max_text_len as u64)
+    }
+
+    pub(crate) fn check_chunks(&self, count: usize) -> Result<()> {
+        check("chunk count", count as u64, self.max_chunks as u64)
+    }
+
+    /// Checks the size of `count` images of `width * height` pixels of 4 samples
+    pub(crate) fn check_decoded<S>(&self, width: u32, height: u32, count: usize) -> Result<()> {
+        let bytes = (width as u64 * height as u64 * 4 * std::mem::size_of::<S>() as u64)
+            .saturating_mul(count as u64);
+        check("decoded bytes", bytes, self.max_decoded_bytes)
+    }
+}
+
+pub(crate) fn check(limit: &'static str, value: u64, max: u64) -> Result<()> {
+    if value > max {
+        return Err(PngError::LimitExceeded { limit, value, max });
+    }
+    Ok(())
+}
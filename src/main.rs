@@ -1,55 +1,269 @@
 use anyhow::Context;
-use draw_image::display_image;
+use color_print::cprintln;
+use png_display::ancillary_chunks::exif::Orientation;
+use png_display::ancillary_chunks::ster::StereoMode;
+use png_display::ancillary_chunks::AncillaryChunks;
+use png_display::apng::AnimationControl;
+use png_display::draw_image::{
+    display_hdr_image, display_image, display_progressive, RenderOptions,
+};
+use png_display::hdr::ToneMapper;
+use png_display::icc::IccTransform;
+use png_display::ihdr::{IhdrChunk, InterlaceMethod};
+use png_display::info::PngInfo;
+use png_display::png_parser::{self, DecodeOptions};
+use png_display::stream_decoder::StreamDecoder;
 use std::env;
 use std::fs::File;
-use std::io::Read;
+use std::io::{BufReader, Read};
 use std::process::ExitCode;
 use std::time::Duration;
 
-pub mod ancillary_chunks;
-pub mod chunk;
-mod color_type;
-pub mod draw_image;
-pub mod filter_apply;
-pub mod ihdr;
-pub mod plte;
-pub mod png_parser;
-pub mod run_n;
+const USAGE: &str =
+    "Usage: png-display [view] <file> [--ignore-orientation] [--stereo=cross|diverging]
+       png-display info <file> [--format text|json]";
 
-fn main_inner() -> anyhow::Result<()> {
-    let filename = std::env::args().nth(1).context("No args passed")?;
+/// Options whose value can follow as a separate argument, which isn't a file name
+const OPTIONS_WITH_VALUE: [&str; 2] = ["--format", "--stereo"];
+
+fn main_inner() -> anyhow::Result<ExitCode> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    // Without a subcommand the file is shown in the viewer
+    match args.first().map(String::as_str) {
+        Some("info") => info(&args[1..]),
+        Some("view") => view(&args[1..]).map(|()| ExitCode::SUCCESS),
+        _ => view(&args).map(|()| ExitCode::SUCCESS),
+    }
+}
+
+fn filename(args: &[String]) -> anyhow::Result<&String> {
+    args.iter()
+        .enumerate()
+        .find(|&(i, arg)| {
+            let is_value = i > 0 && OPTIONS_WITH_VALUE.contains(&args[i - 1].as_str());
+            !arg.starts_with("--") && !is_value
+        })
+        .map(|(_, arg)| arg)
+        .context(USAGE)
+}
+
+/// Value of `--name=value` or `--name value`
+fn option_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter()
+        .enumerate()
+        .find_map(|(i, arg)| match arg.strip_prefix(name)? {
+            "" => args.get(i + 1).map(String::as_str),
+            value => value.strip_prefix('='),
+        })
+}
+
+/// Prints the structure of the file, failing if anything is wrong with it.
+/// The json format is meant for scripts and has no colors.
+fn info(args: &[String]) -> anyhow::Result<ExitCode> {
+    let filename = filename(args)?;
+    let json = match option_value(args, "--format") {
+        None | Some("text") => false,
+        Some("json") => true,
+        Some(other) => anyhow::bail!("Unknown format: {}", other),
+    };
+    let buf = std::fs::read(filename)?;
+    let info = PngInfo::new(&buf);
+    let exit_code = if info.is_ok() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    };
+    if json {
+        println!("{}", serde_json::to_string_pretty(&info)?);
+        return Ok(exit_code);
+    }
+
+    println!("File: {}\n", filename);
+    print!("{}", info);
+    if info.is_ok() {
+        cprintln!("\n<green>OK</green>");
+    } else {
+        cprintln!("\n<red>ERRORS: {}</red>", info.errors.len());
+    }
+    Ok(exit_code)
+}
+
+fn view(args: &[String]) -> anyhow::Result<()> {
+    let filename = filename(args)?;
+    let viewer_args = ViewerArgs::parse(args)?;
+
+    // Files the streaming decoder can't even start on go through the recovering decoder below
+    if let Ok(decoder) = StreamDecoder::new(BufReader::new(File::open(filename)?)) {
+        let is_animated = decoder
+            .chunks
+            .iter()
+            .any(|c| c.chunk_type == AnimationControl::CHUNK_TYPE);
+        // HDR images are tone mapped from the whole 16 bit image, so they aren't streamed
+        let is_hdr = tone_mapper(&decoder.ancillary_chunks_lenient().0).is_some();
+        if matches!(decoder.ihdr.interlace_method, InterlaceMethod::Adam7)
+            && !is_animated
+            && !is_hdr
+        {
+            return display_interlaced(decoder, &viewer_args);
+        }
+    }
 
     let mut file = File::open(filename)?;
     let mut buf = Vec::new();
     file.read_to_end(&mut buf)?;
 
-    let png = png_parser::Png::new(&buf)?;
+    // Damaged files are still shown as far as they can be decoded
+    let options = DecodeOptions {
+        recover: true,
+        ..Default::default()
+    };
+    let png = png_parser::Png::with_options(&buf, &options)?;
+    println!("{:?}", png.ihdr);
+    for warning in &png.warnings {
+        cprintln!("<yellow>Warning: {}</yellow>", warning);
+    }
 
-    let pixels = png.get_pixels()?;
+    let frames = png.get_frames()?;
     png.print_ancillary();
 
-    let bg = png.other_chunks.get_background();
-    let gama = png.other_chunks.get_gama();
+    let num_plays = png
+        .animation
+        .as_ref()
+        .map_or(1, |animation| animation.control.num_plays);
+
+    let options = render_options(&png.ihdr, &png.other_chunks, &viewer_args);
+    // Animations play until their last frame instead of closing like a still image
+    let timeout = match png.animation {
+        Some(_) => None,
+        None => display_timeout(&png.other_chunks),
+    };
+    let tone_mapper = tone_mapper(&png.other_chunks);
+    // Animations are shown without tone mapping, their frames are only composed in 8 bits
+    if let (Some(tone_mapper), None) = (tone_mapper, &png.animation) {
+        return display_hdr_image(&png.get_pixels16()?, &tone_mapper, timeout, &options);
+    }
+    display_image(frames, num_plays, timeout, &options)
+}
+
+/// Viewer flags given after or before the file name
+struct ViewerArgs {
+    /// The EXIF orientation is applied unless `--ignore-orientation` is given
+    apply_orientation: bool,
+    /// Stereo pairs are rearranged into this layout, from `--stereo=cross|diverging`
+    stereo_mode: Option<StereoMode>,
+}
+
+impl ViewerArgs {
+    fn parse(args: &[String]) -> anyhow::Result<Self> {
+        let stereo_mode = match option_value(args, "--stereo") {
+            None => None,
+            Some("cross") => Some(StereoMode::CrossFuse),
+            Some("diverging") => Some(StereoMode::DivergingFuse),
+            Some(other) => anyhow::bail!("Unknown stereo layout: {}", other),
+        };
+        Ok(Self {
+            apply_orientation: !args.iter().any(|arg| arg == "--ignore-orientation"),
+            stereo_mode,
+        })
+    }
+}
+
+fn render_options(ihdr: &IhdrChunk, chunks: &AncillaryChunks, args: &ViewerArgs) -> RenderOptions {
+    let orientation = args
+        .apply_orientation
+        .then(|| chunks.get_exif()?.orientation())
+        .flatten();
+    let stereo = chunks
+        .get_stereo()
+        .map(|stereo| (stereo, args.stereo_mode.unwrap_or(stereo.mode)));
+    RenderOptions {
+        scale: 900.0 / displayed_height(ihdr.width, ihdr.height, orientation),
+        pixel_aspect_ratio: chunks
+            .get_physical_units()
+            .and_then(|units| units.pixel_aspect_ratio())
+            .unwrap_or(1.0),
+        background: chunks.get_background(),
+        gama: chunks.get_gama(),
+        chromaticities: chunks.get_chromaticities(),
+        srgb: chunks.get_srgb(),
+        icc: icc_transform(chunks),
+        orientation,
+        stereo,
+        wait_for_input: chunks
+            .get_gif_graphic_control()
+            .is_some_and(|gif| gif.user_input),
+    }
+}
 
-    display_image(
-        pixels,
-        900.0 / png.ihdr.height as f32,
-        Some(Duration::from_secs_f32(0.3f32)),
-        bg,
-        gama,
+/// How long the image stays up, the gIFg delay if it has one.
+/// A gIFg waiting for user input with no delay keeps it up until a key is pressed.
+fn display_timeout(chunks: &AncillaryChunks) -> Option<Duration> {
+    match chunks.get_gif_graphic_control() {
+        Some(gif) if gif.delay_time > 0 => Some(gif.delay()),
+        Some(gif) if gif.user_input => None,
+        _ => Some(Duration::from_secs_f32(0.3f32)),
+    }
+}
+
+/// Images with an HDR transfer function in their cICP chunk are tone mapped
+fn tone_mapper(chunks: &AncillaryChunks) -> Option<ToneMapper> {
+    ToneMapper::new(
+        &chunks.get_cicp()?,
+        chunks.get_mastering_display().as_ref(),
+        chunks.get_content_light_level().as_ref(),
     )
 }
 
+/// Profiles the viewer can't apply are reported and the image is shown unconverted
+fn icc_transform(chunks: &AncillaryChunks) -> Option<IccTransform> {
+    let transform = chunks.get_iccp()?.transform();
+    if let Err(err) = &transform {
+        cprintln!("<yellow>Warning: {}</yellow>", err);
+    }
+    transform.ok()
+}
+
+fn displayed_height(width: u32, height: u32, orientation: Option<Orientation>) -> f32 {
+    match orientation {
+        Some(orientation) if orientation.swaps_dimensions() => width as f32,
+        _ => height as f32,
+    }
+}
+
+fn display_interlaced(
+    decoder: StreamDecoder<BufReader<File>>,
+    args: &ViewerArgs,
+) -> anyhow::Result<()> {
+    println!("{:?}", decoder.ihdr);
+    let (options, timeout) = {
+        // Damaged ancillary chunks are skipped like in the recovering decoder
+        let (chunks, warnings) = decoder.ancillary_chunks_lenient();
+        for warning in &warnings {
+            cprintln!("<yellow>Warning: {}</yellow>", warning);
+        }
+        for chunk in &chunks.0 {
+            chunk.print();
+            println!();
+        }
+        (
+            render_options(&decoder.ihdr, &chunks, args),
+            display_timeout(&chunks),
+        )
+    };
+
+    display_progressive(decoder, timeout, &options)
+}
+
 fn main() -> ExitCode {
     env::set_var("RUST_LIB_BACKTRACE", "1");
 
-    let res = main_inner();
-    if let Err(err) = &res {
-        println!("Failed: {}", err);
-        print_my_backtrace(err);
-        ExitCode::FAILURE
-    } else {
-        ExitCode::SUCCESS
+    match main_inner() {
+        Ok(exit_code) => exit_code,
+        Err(err) => {
+            println!("Failed: {}", err);
+            print_my_backtrace(&err);
+            ExitCode::FAILURE
+        }
     }
 }
 
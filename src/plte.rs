@@ -1,9 +1,12 @@
-use anyhow::Context;
 use nom::{number::complete::u8, IResult};
 
+use crate::chunk::RawChunk;
+use crate::error::{PngError, Result};
+
 pub const PLTE: &str = "PLTE";
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Palette {
     pub entries: Vec<(u8, u8, u8, u8)>,
 }
@@ -19,10 +22,21 @@ fn read_transparency(trns: &[u8]) -> IResult<&[u8], u8> {
     u8(trns)
 }
 
-pub fn parse_palette<'a>(input: &'a [u8], mut trns: Option<&'a [u8]>) -> anyhow::Result<Palette> {
-    if let Some(trns) = trns {
-        if trns.len() > input.len() / 3 {
-            anyhow::bail!("Transparent chunk to long for palette")
+fn invalid(chunk: &RawChunk, reason: &str) -> PngError {
+    PngError::Palette {
+        chunk_type: chunk.chunk_type.to_string(),
+        offset: chunk.offset,
+        reason: reason.to_string(),
+    }
+}
+
+/// Parses a PLTE chunk along with the alpha of its entries from the tRNS chunk
+pub fn parse_palette(plte: &RawChunk, trns_chunk: Option<&RawChunk>) -> Result<Palette> {
+    let input = plte.data;
+    let mut trns = trns_chunk.map(|chunk| chunk.data);
+    if let Some(trns_chunk) = trns_chunk {
+        if trns_chunk.data.len() > input.len() / 3 {
+            return Err(invalid(trns_chunk, "Transparent chunk to long for palette"));
         }
     }
 
@@ -31,16 +45,14 @@ pub fn parse_palette<'a>(input: &'a [u8], mut trns: Option<&'a [u8]>) -> anyhow:
 
     while !remaining_input.is_empty() {
         let (input, (r, g, b)) = read_pixel(remaining_input)
-            .map_err(|e| e.to_owned())
-            .context("Failed parsing palette pixel")?;
+            .map_err(|_| invalid(plte, "Failed parsing palette pixel"))?;
 
-        let transpareny = if let Some(trns) = trns.as_mut() {
+        let transpareny = if let (Some(trns), Some(trns_chunk)) = (trns.as_mut(), trns_chunk) {
             if trns.is_empty() {
                 255
             } else {
                 let (rest, transpareny) = read_transparency(trns)
-                    .map_err(|e| e.to_owned())
-                    .context("Failed parsing transpareny pixel")?;
+                    .map_err(|_| invalid(trns_chunk, "Failed parsing transpareny pixel"))?;
                 *trns = rest;
 
                 transpareny
@@ -55,3 +67,38 @@ pub fn parse_palette<'a>(input: &'a [u8], mut trns: Option<&'a [u8]>) -> anyhow:
 
     Ok(Palette { entries: palette })
 }
+
+impl Palette {
+    /// Index of the entry closest to the pixel, `None` for an empty palette
+    pub fn nearest(&self, (r, g, b, a): (u8, u8, u8, u8)) -> Option<usize> {
+        let distance = |&(er, eg, eb, ea): &(u8, u8, u8, u8)| {
+            [(r, er), (g, eg), (b, eb), (a, ea)]
+                .iter()
+                .map(|&(x, y)| (x as i32 - y as i32).pow(2))
+                .sum::<i32>()
+        };
+        self.entries
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, entry)| distance(entry))
+            .map(|(i, _)| i)
+    }
+
+    /// Serializes the palette into PLTE content and, if any entry isn't opaque, tRNS content
+    pub fn to_bytes(&self) -> (Vec<u8>, Option<Vec<u8>>) {
+        let plte = self
+            .entries
+            .iter()
+            .flat_map(|&(r, g, b, _)| [r, g, b])
+            .collect();
+
+        let transparent_len = self
+            .entries
+            .iter()
+            .rposition(|&(_, _, _, a)| a != 255)
+            .map(|i| i + 1);
+        let trns = transparent_len.map(|len| self.entries[..len].iter().map(|e| e.3).collect());
+
+        (plte, trns)
+    }
+}
@@ -1,24 +1,83 @@
-use crate::ancillary_chunks::{parse_ancillary_chunks, AncillaryChunks};
+use crate::ancillary_chunks::{
+    parse_ancillary_chunks, parse_ancillary_chunks_lenient, AncillaryChunks,
+};
+use crate::apng::{self, take_animation, Animation, DisposeOp, Frame};
 use crate::chunk::RawChunk;
+use crate::chunk_order::{validate_chunk_order, OrderValidation};
+use crate::color_type::ColorType;
+use crate::error::{PngError, Result};
 use crate::filter_apply;
-use crate::ihdr::{self, IhdrChunk};
-use anyhow::{anyhow, Context};
+use crate::ihdr::{self, IhdrChunk, IHDR};
+use crate::image_buffer::ImageBuffer;
+use crate::limits::{self, DecodeLimits};
+use crate::raw_image::{copy_pixel_bits, RawImage};
 use bitreader::BitReader;
+use color_print::cprintln;
+use inflate::InflateStream;
 use nom::{bytes::complete::tag, IResult};
+use std::time::Duration;
 
-use crate::chunk::parse_chunks;
+use crate::chunk::{parse_chunks, parse_chunks_lenient};
 use crate::ihdr::parse_ihdr;
 use crate::plte::{self, parse_palette, Palette};
 
 pub type Pixel = (u8, u8, u8, u8);
-pub type Image = Vec<Vec<Pixel>>;
+pub type Image = ImageBuffer<u8>;
+pub type Pixel16 = (u16, u16, u16, u16);
+pub type Image16 = ImageBuffer<u16>;
+
+type ReadPixel<S> = fn(&ColorType, u8, &mut BitReader) -> Result<(S, S, S, S)>;
+
+pub fn pixel16_to_pixel((r, g, b, a): Pixel16) -> Pixel {
+    (
+        (r >> 8) as u8,
+        (g >> 8) as u8,
+        (b >> 8) as u8,
+        (a >> 8) as u8,
+    )
+}
 
 pub const TRNS: &str = "tRNS";
+pub const IDAT: &str = "IDAT";
+pub const IEND: &str = "IEND";
+pub const MAGIC_NUMBER: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+
+/// ((start_x, start_y), (step_x, step_y)) of every Adam7 pass
+pub const ADAM7_PASSES: [((usize, usize), (usize, usize)); 7] = [
+    ((0, 0), (8, 8)),
+    ((4, 0), (8, 8)),
+    ((0, 4), (4, 8)),
+    ((2, 0), (4, 4)),
+    ((0, 2), (2, 4)),
+    ((1, 0), (2, 2)),
+    ((0, 1), (1, 2)),
+];
+
+#[derive(Debug, Clone, Default)]
+pub struct DecodeOptions {
+    /// Whether chunks out of order or repeated abort decoding, by default they're only warnings
+    pub chunk_order: OrderValidation,
+    /// Decodes as much of a damaged file as possible instead of failing:
+    /// chunks that can't be read are skipped and missing image data is left blank.
+    /// Chunk order violations are always warnings in this mode.
+    pub recover: bool,
+    pub limits: DecodeLimits,
+}
 
 pub struct Png<'a> {
     pub ihdr: IhdrChunk,
     pub data: Vec<u8>,
+    /// Length of the data inflated from the IDAT chunks, `data` is padded with zeros past it
+    /// when recovering from missing image data
+    pub inflated_len: usize,
+    pub animation: Option<Animation>,
     pub other_chunks: AncillaryChunks<'a>,
+    /// Problems that didn't abort decoding
+    pub warnings: Vec<PngError>,
+    /// Bounds the images decoded from the data
+    limits: DecodeLimits,
+    /// Offset of the first IDAT chunk, errors in the image data are reported at it
+    idat_offset: usize,
 }
 
 fn take_chunk<'a>(chunks: &mut Vec<RawChunk<'a>>, chunk_type: &str) -> Option<RawChunk<'a>> {
@@ -28,28 +87,31 @@ fn take_chunk<'a>(chunks: &mut Vec<RawChunk<'a>>, chunk_type: &str) -> Option<Ra
         .map(|i| chunks.remove(i))
 }
 
-fn take_palette_chunk(chunks: &mut Vec<RawChunk>) -> anyhow::Result<Option<Palette>> {
+fn take_palette_chunk(chunks: &mut Vec<RawChunk>) -> Result<Option<Palette>> {
     let plte = take_chunk(chunks, plte::PLTE);
     if let Some(plte) = plte {
-        let trns = take_chunk(chunks, TRNS).map(|c| c.data);
-        let palette = parse_palette(plte.data, trns)?;
+        let trns = take_chunk(chunks, TRNS);
+        let palette = parse_palette(&plte, trns.as_ref())?;
         Ok(Some(palette))
     } else {
         Ok(None)
     }
 }
 
-const IDAT: &str = "IDAT";
-fn take_idta_chunks(chunks: &mut Vec<RawChunk>) -> anyhow::Result<Vec<u8>> {
+/// Returns the concatenated compressed data of the IDAT chunks and the offset of the first one
+fn take_idta_chunks(chunks: &mut Vec<RawChunk>) -> Result<(Vec<u8>, usize)> {
     let first_idat_index = chunks
         .iter()
         .position(|elem| elem.chunk_type == IDAT)
-        .context("No idat chunk")?;
+        .ok_or(PngError::MissingChunk(IDAT))?;
+    let first_idat_offset = chunks[first_idat_index].offset;
 
+    // IDAT chunks are only split apart when the chunk order isn't enforced,
+    // their data is still concatenated in order
     let idat_indexes = chunks[first_idat_index..]
         .iter()
         .enumerate()
-        .take_while(|(_, elem)| elem.chunk_type == IDAT)
+        .filter(|(_, elem)| elem.chunk_type == IDAT)
         .map(|(i, _)| i + first_idat_index)
         .collect::<Vec<_>>();
 
@@ -64,25 +126,79 @@ fn take_idta_chunks(chunks: &mut Vec<RawChunk>) -> anyhow::Result<Vec<u8>> {
         chunks.remove(index);
     }
 
-    inflate::inflate_bytes_zlib(&data)
-        .map_err(|e| anyhow!("Failed deompressing image data: {:?}", e))
+    Ok((data, first_idat_offset))
+}
+
+/// Inflates a zlib stream, failing once more than `max_len` bytes come out (`limit` names the
+/// limit in the error). A corrupted stream doesn't fail, the data inflated before the corruption
+/// is returned along with the reason. A truncated stream simply yields less data.
+pub(crate) fn inflate_limited(
+    data: &[u8],
+    limit: &'static str,
+    max_len: usize,
+) -> Result<(Vec<u8>, Option<String>)> {
+    let mut inflater = InflateStream::from_zlib();
+    let mut inflated = Vec::new();
+    let mut consumed = 0;
+    loop {
+        match inflater.update(&data[consumed..]) {
+            Ok((read, output)) => {
+                consumed += read;
+                if output.is_empty() && (read == 0 || consumed == data.len()) {
+                    return Ok((inflated, None));
+                }
+                limits::check(
+                    limit,
+                    (inflated.len() + output.len()) as u64,
+                    max_len as u64,
+                )?;
+                inflated.extend_from_slice(output);
+            }
+            Err(e) => return Ok((inflated, Some(e))),
+        }
+    }
+}
+
+/// Inflates the zlib stream of a chunk like [`inflate_limited`], failing if it is corrupted
+pub(crate) fn inflate_chunk(
+    data: &[u8],
+    chunk_type: &str,
+    limit: &'static str,
+    max_len: usize,
+) -> Result<Vec<u8>> {
+    match inflate_limited(data, limit, max_len)? {
+        (inflated, None) => Ok(inflated),
+        (_, Some(reason)) => Err(PngError::decompression(chunk_type, reason)),
+    }
 }
 
 impl<'a> Png<'a> {
-    pub fn new(input: &'a [u8]) -> anyhow::Result<Self> {
-        const MAGIC_NUMBER: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+    pub fn new(input: &'a [u8]) -> Result<Self> {
+        Self::with_options(input, &DecodeOptions::default())
+    }
 
+    pub fn with_options(input: &'a [u8], options: &DecodeOptions) -> Result<Self> {
         fn read_magic_number(input: &[u8]) -> IResult<&[u8], ()> {
             let (input, _) = tag(MAGIC_NUMBER)(input)?;
             Ok((input, ()))
         }
-        let (input, _) = read_magic_number(input)
-            .map_err(|e| e.to_owned())
-            .context("Invalid magic number")?;
+        let (input, _) = read_magic_number(input).map_err(|_| PngError::Signature)?;
 
-        let mut chunks = parse_chunks(input)?;
+        let (mut chunks, mut warnings) = if options.recover {
+            parse_chunks_lenient(input, MAGIC_NUMBER.len())
+        } else {
+            (parse_chunks(input, MAGIC_NUMBER.len())?, Vec::new())
+        };
+        let chunk_order = if options.recover {
+            OrderValidation::WarnOnly
+        } else {
+            options.chunk_order
+        };
+        options.limits.check_chunks(chunks.len())?;
+        warnings.extend(validate_chunk_order(&chunks, chunk_order)?);
 
         let palette = take_palette_chunk(&mut chunks)?;
+        let palette_len = palette.as_ref().map(|palette| palette.entries.len());
         let trns = if palette.is_some() {
             None
         } else {
@@ -90,36 +206,156 @@ impl<'a> Png<'a> {
         }
         .map(|c| c.data);
 
-        let ihdr = chunks.remove(0);
-        let (_, ihdr) = parse_ihdr(ihdr.data, palette, trns)?;
-        println!("{:?}", ihdr);
-
-        let data = take_idta_chunks(&mut chunks)?;
+        let ihdr = take_chunk(&mut chunks, IHDR).ok_or(PngError::MissingChunk(IHDR))?;
+        let (_, ihdr) = parse_ihdr(ihdr.data, ihdr.offset, palette, trns, &options.limits)?;
 
-        const IEND: &str = "IEND";
-        let iend = chunks.remove(chunks.len() - 1);
-        if iend.chunk_type != IEND {
-            anyhow::bail!("Last chunk isn't IEND");
+        let animation = match take_animation(&mut chunks, ihdr.width, ihdr.height, &options.limits)
+        {
+            Err(err) if options.recover => {
+                warnings.push(err);
+                None
+            }
+            animation => animation?,
+        };
+
+        let (compressed, idat_offset) = take_idta_chunks(&mut chunks)?;
+        let expected_len = ihdr.image_data_len();
+        limits::check(
+            "inflated bytes",
+            expected_len as u64,
+            options.limits.max_inflated_bytes as u64,
+        )?;
+        let (mut data, error) = inflate_limited(
+            &compressed,
+            "inflated bytes",
+            options.limits.max_inflated_bytes,
+        )?;
+        if let Some(reason) = error {
+            let err = PngError::decompression(IDAT, reason).at_offset(idat_offset);
+            if !options.recover {
+                return Err(err);
+            }
+            warnings.push(err);
+        }
+        let inflated_len = data.len();
+        if options.recover && data.len() < expected_len {
+            warnings.push(
+                PngError::image_data(format!(
+                    "Image data ends after {} of {} bytes, the missing rows are left blank",
+                    data.len(),
+                    expected_len
+                ))
+                .at_offset(idat_offset),
+            );
+            data.resize(expected_len, 0);
         }
-        if !iend.data.is_empty() {
-            anyhow::bail!("IEND isn't empty")
+
+        match take_chunk(&mut chunks, IEND) {
+            Some(iend) if !iend.data.is_empty() => {
+                let err = PngError::ChunkOrder {
+                    chunk_type: IEND.to_string(),
+                    offset: iend.offset,
+                    reason: "IEND isn't empty".to_string(),
+                };
+                if !options.recover {
+                    return Err(err);
+                }
+                warnings.push(err);
+            }
+            Some(_) => {}
+            None if options.recover => warnings.push(PngError::MissingChunk(IEND)),
+            None => return Err(PngError::MissingChunk(IEND)),
         }
-        let non_requied_chunks = parse_ancillary_chunks(chunks, &ihdr)?;
+        // Repeated critical chunks are only left when the chunk order isn't enforced
+        chunks.retain(|c| ![IHDR, plte::PLTE, IEND].contains(&c.chunk_type));
+        let non_requied_chunks = if options.recover {
+            let (ancillary, ancillary_warnings) =
+                parse_ancillary_chunks_lenient(chunks, &ihdr, palette_len, &options.limits);
+            warnings.extend(ancillary_warnings);
+            ancillary
+        } else {
+            parse_ancillary_chunks(chunks, &ihdr, palette_len, &options.limits)?
+        };
         Ok(Self {
             ihdr,
             data,
+            inflated_len,
+            animation,
             other_chunks: AncillaryChunks(non_requied_chunks),
+            warnings,
+            limits: options.limits.clone(),
+            idat_offset,
         })
     }
 
-    pub fn get_pixels(&self) -> anyhow::Result<Image> {
-        match self.ihdr.interlace_method {
-            ihdr::InterlaceMethod::Adam7 => self.get_pixels_adam7(),
-            ihdr::InterlaceMethod::None => self.get_pixels_no_interlace(),
+    pub fn get_pixels(&self) -> Result<Image> {
+        self.decode_pixels(&self.data, self.ihdr.width, self.ihdr.height)
+            .map_err(|e| e.at_offset(self.idat_offset))
+    }
+
+    /// Decodes the image without truncating 16 bit samples,
+    /// lower bit depths are scaled up to the 16 bit range
+    pub fn get_pixels16(&self) -> Result<Image16> {
+        self.decode_pixels16(&self.data, self.ihdr.width, self.ihdr.height)
+            .map_err(|e| e.at_offset(self.idat_offset))
+    }
+
+    /// Renders every frame of the animation onto the full canvas.
+    /// A png without an animation yields the default image as a single frame.
+    pub fn get_frames(&self) -> Result<Vec<Frame>> {
+        let Some(animation) = &self.animation else {
+            return Ok(vec![Frame {
+                image: self.get_pixels()?,
+                delay: Duration::ZERO,
+            }]);
+        };
+
+        // Every composed frame is kept, along with the canvas and its copy saved for disposal
+        self.limits.check_decoded::<u8>(
+            self.ihdr.width,
+            self.ihdr.height,
+            animation.frames.len() + 2,
+        )?;
+        let mut canvas = Image::new(self.ihdr.width, self.ihdr.height);
+        let mut frames = Vec::with_capacity(animation.frames.len());
+
+        for (i, frame) in animation.frames.iter().enumerate() {
+            let control = &frame.control;
+            let frame_image = match &frame.data {
+                Some(data) => self
+                    .decode_pixels(data, control.width, control.height)
+                    .map_err(|e| e.at_offset(frame.offset))?,
+                None => self.get_pixels()?,
+            };
+
+            // The first frame can't restore to a previous frame
+            let dispose_op = if i == 0 && control.dispose_op == DisposeOp::Previous {
+                DisposeOp::Background
+            } else {
+                control.dispose_op
+            };
+            let previous = (dispose_op == DisposeOp::Previous).then(|| canvas.clone());
+
+            apng::compose_frame(&mut canvas, &frame_image, control);
+            frames.push(Frame {
+                image: canvas.clone(),
+                delay: control.delay(),
+            });
+            apng::dispose_frame(&mut canvas, previous.as_ref(), control, dispose_op);
         }
+
+        Ok(frames)
     }
 
     pub fn print_ancillary(&self) {
+        if let Some(animation) = &self.animation {
+            cprintln!(
+                "<cyan>Chunk Type: {}</cyan>",
+                apng::AnimationControl::CHUNK_TYPE
+            );
+            cprintln!("<green>{}</green>", animation.control);
+            println!();
+        }
         for chunk in &self.other_chunks.0 {
             chunk.print();
             println!();
@@ -127,100 +363,342 @@ impl<'a> Png<'a> {
     }
 
     fn bpp(&self) -> usize {
-        let values_per_pixel = self.ihdr.color_type.values_per_pixel();
-        (self.ihdr.bit_depth.div_ceil(8) * values_per_pixel) as usize
-    }
-    fn bpp_float(&self) -> f32 {
-        let values_per_pixel = self.ihdr.color_type.values_per_pixel() as f32;
-        self.ihdr.bit_depth as f32 / 8.0 * values_per_pixel
+        self.ihdr.bytes_per_pixel()
     }
 
-    fn get_pixels_no_interlace(&self) -> anyhow::Result<Image> {
-        let mut bitreader = BitReader::new(&self.data[..]);
-
-        let mut pixels =
-            vec![vec![(0, 0, 0, 0); self.ihdr.width as usize]; self.ihdr.height as usize];
-
-        let values_per_pixel = self.ihdr.color_type.values_per_pixel() as u32;
-        let bits_in_scanline = self.ihdr.bit_depth as u32 * values_per_pixel * self.ihdr.width;
-        let scanline_len = 1 + (bits_in_scanline as usize).div_ceil(8);
+    /// Reads and unfilters `height` scanlines of `width` pixels,
+    /// calling `on_scanline` with the index and content of every decoded scanline
+    fn unfilter_scanlines(
+        &self,
+        bitreader: &mut BitReader,
+        width: usize,
+        height: usize,
+        mut on_scanline: impl FnMut(usize, &[u8]) -> Result<()>,
+    ) -> Result<()> {
+        let scanline_len = 1 + self.ihdr.scanline_len(width as u32);
 
         let mut scanline = vec![0; scanline_len];
         let mut prev_scanline = vec![0; scanline_len - 1];
         let mut decoded = vec![0; scanline_len - 1];
 
         let bpp = self.bpp();
-        for i in 0..self.ihdr.height {
+        for i in 0..height {
             bitreader.read_u8_slice(&mut scanline)?;
 
             filter_apply::decode_scanline(&scanline[..], &prev_scanline[..], bpp, &mut decoded)?;
+            on_scanline(i, &decoded)?;
+
+            prev_scanline.clone_from_slice(&decoded[..]);
+        }
+        Ok(())
+    }
 
-            let mut scanline_reader = BitReader::new(&decoded);
+    fn decode_pixels(&self, data: &[u8], width: u32, height: u32) -> Result<Image> {
+        match self.other_chunks.get_sbit() {
+            Some(_) => Ok(self.decode_pixels16(data, width, height)?.to_8bit()),
+            None => self.decode_pixels_with(data, width, height, ColorType::read_pixel),
+        }
+    }
 
-            for j in 0..self.ihdr.width {
-                pixels[i as usize][j as usize] = self
-                    .ihdr
-                    .color_type
-                    .read_pixel(self.ihdr.bit_depth, &mut scanline_reader)?;
+    /// Samples with fewer significant bits than the bit depth, according to sBIT,
+    /// are rescaled from their significant bits
+    fn decode_pixels16(&self, data: &[u8], width: u32, height: u32) -> Result<Image16> {
+        let mut pixels = self.decode_pixels_with(data, width, height, ColorType::read_pixel16)?;
+        if let Some(sbit) = self.other_chunks.get_sbit() {
+            let sample_depth = self.ihdr.sample_depth();
+            for pixel in pixels.samples_mut().chunks_exact_mut(4) {
+                let (r, g, b, a) =
+                    sbit.rescale((pixel[0], pixel[1], pixel[2], pixel[3]), sample_depth);
+                pixel.copy_from_slice(&[r, g, b, a]);
             }
+        }
+        Ok(pixels)
+    }
 
-            prev_scanline.clone_from_slice(&decoded[..]);
+    fn decode_pixels_with<S: Copy + Default>(
+        &self,
+        data: &[u8],
+        width: u32,
+        height: u32,
+        read_pixel: ReadPixel<S>,
+    ) -> Result<ImageBuffer<S>> {
+        self.limits.check_decoded::<S>(width, height, 1)?;
+        match self.ihdr.interlace_method {
+            ihdr::InterlaceMethod::Adam7 => self.get_pixels_adam7(data, width, height, read_pixel),
+            ihdr::InterlaceMethod::None => {
+                self.get_pixels_no_interlace(data, width, height, read_pixel)
+            }
         }
+    }
+
+    fn get_pixels_no_interlace<S: Copy + Default>(
+        &self,
+        data: &[u8],
+        width: u32,
+        height: u32,
+        read_pixel: ReadPixel<S>,
+    ) -> Result<ImageBuffer<S>> {
+        let mut bitreader = BitReader::new(data);
+
+        let mut pixels = ImageBuffer::new(width, height);
+
+        self.unfilter_scanlines(
+            &mut bitreader,
+            width as usize,
+            height as usize,
+            |i, decoded| {
+                let mut scanline_reader = BitReader::new(decoded);
+
+                for j in 0..width {
+                    let pixel = read_pixel(
+                        &self.ihdr.color_type,
+                        self.ihdr.bit_depth,
+                        &mut scanline_reader,
+                    )?;
+                    pixels.set_pixel(j, i as u32, pixel);
+                }
+                Ok(())
+            },
+        )?;
 
         Ok(pixels)
     }
 
-    fn get_pixels_adam7(&self) -> anyhow::Result<Image> {
-        let adam7_passes: [((usize, usize), (usize, usize)); 7] = [
-            ((0, 0), (8, 8)),
-            ((4, 0), (8, 8)),
-            ((0, 4), (4, 8)),
-            ((2, 0), (4, 4)),
-            ((0, 2), (2, 4)),
-            ((1, 0), (2, 2)),
-            ((0, 1), (1, 2)),
-        ];
-
-        let mut bitreader = BitReader::new(&self.data[..]);
+    fn get_pixels_adam7<S: Copy + Default>(
+        &self,
+        data: &[u8],
+        width: u32,
+        height: u32,
+        read_pixel: ReadPixel<S>,
+    ) -> Result<ImageBuffer<S>> {
+        let mut bitreader = BitReader::new(data);
 
-        let mut pixels =
-            vec![vec![(0, 0, 0, 0); self.ihdr.width as usize]; self.ihdr.height as usize];
+        let mut pixels = ImageBuffer::new(width, height);
 
-        for ((start_x, start_y), (step_x, step_y)) in adam7_passes {
-            if (self.ihdr.width as usize) <= start_x || (self.ihdr.height as usize) <= start_y {
+        for ((start_x, start_y), (step_x, step_y)) in ADAM7_PASSES {
+            if (width as usize) <= start_x || (height as usize) <= start_y {
                 continue;
             }
-            let scanline_len = 1.0
-                + (((self.ihdr.width as usize - start_x).div_ceil(step_x)) as f32
-                    * self.bpp_float())
-                .ceil();
-
-            let scanline_len = scanline_len as usize;
-            let mut scanline = vec![0; scanline_len];
-            let mut prev_scanline = vec![0; scanline_len - 1];
-            let mut decoded = vec![0; scanline_len - 1];
-            for i in (start_y..self.ihdr.height as usize).step_by(step_y) {
-                let bpp = self.bpp();
-                bitreader.read_u8_slice(&mut scanline)?;
-
-                filter_apply::decode_scanline(
-                    &scanline[..],
-                    &prev_scanline[..],
-                    bpp,
-                    &mut decoded,
-                )?;
+            let pass_width = (width as usize - start_x).div_ceil(step_x);
+            let pass_height = (height as usize - start_y).div_ceil(step_y);
+
+            self.unfilter_scanlines(&mut bitreader, pass_width, pass_height, |i, decoded| {
+                let y = start_y + i * step_y;
+                let mut scanline_reader = BitReader::new(decoded);
+                for x in (start_x..width as usize).step_by(step_x) {
+                    let pixel = read_pixel(
+                        &self.ihdr.color_type,
+                        self.ihdr.bit_depth,
+                        &mut scanline_reader,
+                    )?;
+                    pixels.set_pixel(x as u32, y as u32, pixel);
+                }
+                Ok(())
+            })?;
+        }
+
+        Ok(pixels)
+    }
+
+    /// Returns the unfiltered image data in the native layout of the png:
+    /// packed samples of the original bit depth, palette indices for indexed images.
+    /// Interlaced images are deinterlaced into full rows.
+    pub fn get_raw(&self) -> Result<RawImage<'_>> {
+        let (width, height) = (self.ihdr.width as usize, self.ihdr.height as usize);
+        let stride = self.ihdr.scanline_len(self.ihdr.width);
+        let mut data = vec![0; stride * height];
+        let mut bitreader = BitReader::new(&self.data[..]);
 
-                let mut scanline_reader = BitReader::new(&decoded);
-                for j in (start_x..self.ihdr.width as usize).step_by(step_x) {
-                    pixels[i][j] = self
-                        .ihdr
-                        .color_type
-                        .read_pixel(self.ihdr.bit_depth, &mut scanline_reader)?;
+        match self.ihdr.interlace_method {
+            ihdr::InterlaceMethod::None => {
+                self.unfilter_scanlines(&mut bitreader, width, height, |i, decoded| {
+                    data[i * stride..(i + 1) * stride].copy_from_slice(decoded);
+                    Ok(())
+                })
+                .map_err(|e| e.at_offset(self.idat_offset))?;
+            }
+            ihdr::InterlaceMethod::Adam7 => {
+                let bits_per_pixel = self.ihdr.bits_per_pixel();
+                for ((start_x, start_y), (step_x, step_y)) in ADAM7_PASSES {
+                    if width <= start_x || height <= start_y {
+                        continue;
+                    }
+                    let pass_width = (width - start_x).div_ceil(step_x);
+                    let pass_height = (height - start_y).div_ceil(step_y);
+
+                    self.unfilter_scanlines(
+                        &mut bitreader,
+                        pass_width,
+                        pass_height,
+                        |i, decoded| {
+                            let y = start_y + i * step_y;
+                            let row = &mut data[y * stride..(y + 1) * stride];
+                            for (pass_x, x) in (start_x..width).step_by(step_x).enumerate() {
+                                copy_pixel_bits(decoded, pass_x, row, x, bits_per_pixel);
+                            }
+                            Ok(())
+                        },
+                    )
+                    .map_err(|e| e.at_offset(self.idat_offset))?;
                 }
-                prev_scanline.clone_from_slice(&decoded[..]);
             }
         }
 
-        Ok(pixels)
+        Ok(RawImage {
+            width: self.ihdr.width,
+            height: self.ihdr.height,
+            bit_depth: self.ihdr.bit_depth,
+            color_type: &self.ihdr.color_type,
+            stride,
+            data,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ihdr::InterlaceMethod;
+    use crate::test_support::rgba_png;
+
+    fn options(limits: DecodeLimits) -> DecodeOptions {
+        DecodeOptions {
+            limits,
+            ..Default::default()
+        }
+    }
+
+    /// A 1x1 png made of the given chunks between IHDR and IEND
+    fn png_with_chunks(ihdr: [u8; 13], chunks: &[(&str, Vec<u8>)]) -> Vec<u8> {
+        let mut png = MAGIC_NUMBER.to_vec();
+        crate::chunk::write_chunk(&mut png, IHDR, &ihdr).unwrap();
+        for (chunk_type, data) in chunks {
+            crate::chunk::write_chunk(&mut png, chunk_type, data).unwrap();
+        }
+        crate::chunk::write_chunk(&mut png, IEND, &[]).unwrap();
+        png
+    }
+
+    #[test]
+    fn errors_report_the_chunk_offset() {
+        // IHDR comes right after the signature, the following chunk after its 25 bytes
+        const RGBA: [u8; 13] = [0, 0, 0, 1, 0, 0, 0, 1, 8, 6, 0, 0, 0];
+        let mut bad_depth = RGBA;
+        bad_depth[8] = 7;
+        let png = png_with_chunks(bad_depth, &[]);
+        let err = Png::new(&png).err().unwrap();
+        assert!(matches!(err, PngError::Ihdr { offset: 8, .. }));
+        assert_eq!(err.chunk_type(), Some(IHDR));
+
+        let mut indexed = RGBA;
+        indexed[9] = 3;
+        let png = png_with_chunks(indexed, &[(plte::PLTE, vec![0; 4])]);
+        let err = Png::new(&png).err().unwrap();
+        assert!(matches!(err, PngError::Palette { offset: 33, .. }));
+        assert_eq!(err.chunk_type(), Some(plte::PLTE));
+
+        let data = deflate::deflate_bytes_zlib(&[5, 0, 0, 0, 0]);
+        let png = png_with_chunks(RGBA, &[(IDAT, data)]);
+        let err = Png::new(&png).unwrap().get_pixels().err().unwrap();
+        assert!(matches!(
+            err,
+            PngError::Filter {
+                filter_type: 5,
+                offset: 33
+            }
+        ));
+    }
+
+    #[test]
+    fn misplaced_chunks_are_warnings_unless_strict() {
+        // A gAMA chunk after the image data, just before IEND
+        let mut png = rgba_png(4, 4, InterlaceMethod::None);
+        let iend = png.split_off(png.len() - 12);
+        crate::chunk::write_chunk(&mut png, "gAMA", &45455u32.to_be_bytes()).unwrap();
+        png.extend(iend);
+
+        let decoded = Png::new(&png).unwrap();
+        assert!(matches!(
+            decoded.warnings[..],
+            [PngError::ChunkOrder { .. }]
+        ));
+
+        let strict = DecodeOptions {
+            chunk_order: OrderValidation::Strict,
+            ..Default::default()
+        };
+        assert!(matches!(
+            Png::with_options(&png, &strict),
+            Err(PngError::ChunkOrder { .. })
+        ));
+    }
+
+    #[test]
+    fn truncated_files_are_recovered() {
+        let png = rgba_png(16, 16, InterlaceMethod::None);
+        let truncated = &png[..png.len() / 2];
+        assert!(Png::new(truncated).is_err());
+
+        let recover = DecodeOptions {
+            recover: true,
+            ..Default::default()
+        };
+        let decoded = Png::with_options(truncated, &recover).unwrap();
+        assert!(!decoded.warnings.is_empty());
+        assert!(decoded.inflated_len < decoded.ihdr.image_data_len());
+        assert_eq!(decoded.data.len(), decoded.ihdr.image_data_len());
+
+        // The rows that were inflated are intact, the rest is left blank
+        let image = decoded.get_pixels().unwrap();
+        let expected = Png::new(&png).unwrap().get_pixels().unwrap();
+        assert_eq!(image.pixel(0, 0), expected.pixel(0, 0));
+        assert_eq!(image.pixel(15, 15), (0, 0, 0, 0));
+    }
+
+    #[test]
+    fn oversized_images_are_rejected() {
+        let png = rgba_png(64, 64, InterlaceMethod::None);
+        let limits = DecodeLimits {
+            max_width: 63,
+            ..Default::default()
+        };
+        let err = Png::with_options(&png, &options(limits)).err().unwrap();
+        assert!(matches!(
+            err,
+            PngError::LimitExceeded { limit: "width", .. }
+        ));
+
+        let limits = DecodeLimits {
+            max_inflated_bytes: 1000,
+            ..Default::default()
+        };
+        let err = Png::with_options(&png, &options(limits)).err().unwrap();
+        assert!(matches!(
+            err,
+            PngError::LimitExceeded {
+                limit: "inflated bytes",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn decoded_images_are_bounded() {
+        let png = rgba_png(64, 64, InterlaceMethod::None);
+        // Room for the 8 bit image but not for the 16 bit one
+        let limits = DecodeLimits {
+            max_decoded_bytes: 64 * 64 * 4,
+            ..Default::default()
+        };
+        let png = Png::with_options(&png, &options(limits)).unwrap();
+        assert!(png.get_pixels().is_ok());
+        assert!(png.get_frames().is_ok());
+        assert!(matches!(
+            png.get_pixels16(),
+            Err(PngError::LimitExceeded {
+                limit: "decoded bytes",
+                ..
+            })
+        ));
     }
 }
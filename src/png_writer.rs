@@ -0,0 +1,319 @@
+use std::io::Write;
+
+use crate::ancillary_chunks::{AncillaryChunk, AncillaryChunks};
+use crate::chunk::{is_safe_to_copy, write_chunk};
+use crate::chunk_order::BEFORE_PLTE;
+use crate::color_type::ColorType;
+use crate::error::{PngError, Result};
+use crate::filter_apply;
+use crate::ihdr::{CompressionMethod, FilterMethod, IhdrChunk, InterlaceMethod, IHDR};
+use crate::image_buffer::ImageBuffer;
+use crate::plte::{Palette, PLTE};
+use crate::png_parser::{Image, Image16, ADAM7_PASSES, IDAT, IEND, MAGIC_NUMBER, TRNS};
+use crate::raw_image::copy_pixel_bits;
+
+#[derive(Debug, Clone)]
+pub struct WriteOptions {
+    /// Maximum length of the data of a single IDAT chunk
+    pub idat_chunk_size: usize,
+    /// Whether chunks that aren't safe to copy, like sBIT, hIST, bKGD and sPLT, are written.
+    /// Only set it when the image data and palette are the ones the chunks were read with,
+    /// the writer only drops or converts the ones that can't describe the new image.
+    pub copy_unsafe_chunks: bool,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        Self {
+            idat_chunk_size: 8192,
+            copy_unsafe_chunks: false,
+        }
+    }
+}
+
+/// Chunks that the spec requires to appear before PLTE
+fn precedes_palette(chunk_type: &str) -> bool {
+    BEFORE_PLTE.contains(&chunk_type)
+}
+
+fn rgba_ihdr<S: Copy + Default>(image: &ImageBuffer<S>, bit_depth: u8) -> Result<IhdrChunk> {
+    if image.width() == 0 || image.height() == 0 {
+        return Err(PngError::InvalidImage(
+            "Can't write an empty image".to_string(),
+        ));
+    }
+
+    Ok(IhdrChunk {
+        width: image.width(),
+        height: image.height(),
+        bit_depth,
+        color_type: ColorType::Rgba,
+        compression_method: CompressionMethod::Zlib,
+        filter_method: FilterMethod::FiveFilter,
+        interlace_method: InterlaceMethod::None,
+    })
+}
+
+/// Writes an RGBA image as an 8 bit RGBA png
+pub fn write_image<W: Write>(
+    writer: W,
+    image: &Image,
+    ancillary: &AncillaryChunks,
+    options: &WriteOptions,
+) -> Result<()> {
+    let ihdr = rgba_ihdr(image, 8)?;
+    write_raw(writer, image.as_bytes(), &ihdr, ancillary, options)
+}
+
+/// Writes an RGBA image as a 16 bit RGBA png
+pub fn write_image16<W: Write>(
+    writer: W,
+    image: &Image16,
+    ancillary: &AncillaryChunks,
+    options: &WriteOptions,
+) -> Result<()> {
+    let ihdr = rgba_ihdr(image, 16)?;
+
+    let data = image
+        .samples()
+        .iter()
+        .flat_map(|s| s.to_be_bytes())
+        .collect::<Vec<_>>();
+
+    write_raw(writer, &data, &ihdr, ancillary, options)
+}
+
+/// Writes unfiltered scanlines laid out as described by `ihdr`.
+/// Each row of `data` starts on a byte boundary and rows aren't prefixed by a filter byte.
+pub fn write_raw<W: Write>(
+    mut writer: W,
+    data: &[u8],
+    ihdr: &IhdrChunk,
+    ancillary: &AncillaryChunks,
+    options: &WriteOptions,
+) -> Result<()> {
+    if !ihdr.color_type.valid_bit_depths().contains(&ihdr.bit_depth) {
+        return Err(PngError::InvalidImage(format!(
+            "Invalid bit_depth: {}",
+            ihdr.bit_depth
+        )));
+    }
+    let expected_len = ihdr.scanline_len(ihdr.width) * ihdr.height as usize;
+    if data.len() != expected_len {
+        return Err(PngError::InvalidImage(format!(
+            "Image data length {} doesn't match the ihdr, expected {}",
+            data.len(),
+            expected_len
+        )));
+    }
+    if options.idat_chunk_size == 0 {
+        return Err(PngError::InvalidImage(
+            "IDAT chunk size can't be 0".to_string(),
+        ));
+    }
+
+    writer.write_all(&MAGIC_NUMBER)?;
+    write_chunk(&mut writer, IHDR, &ihdr.to_bytes())?;
+
+    let (before_palette, after_palette): (Vec<_>, Vec<_>) = ancillary
+        .0
+        .iter()
+        .filter(|chunk| chunk.fits(ihdr))
+        .filter(|chunk| is_safe_to_copy(chunk.chunk_type()) || options.copy_unsafe_chunks)
+        .partition(|chunk| precedes_palette(chunk.chunk_type()));
+
+    write_ancillary(&mut writer, &before_palette, ihdr)?;
+
+    if let ColorType::Palette(palette) = &ihdr.color_type {
+        write_palette(&mut writer, palette)?;
+    } else if let Some(trns) = ihdr.color_type.trns_bytes() {
+        write_chunk(&mut writer, TRNS, &trns)?;
+    }
+
+    write_ancillary(&mut writer, &after_palette, ihdr)?;
+
+    let filtered = match ihdr.interlace_method {
+        InterlaceMethod::None => filter_image(data, ihdr, ihdr.width, ihdr.height),
+        InterlaceMethod::Adam7 => filter_adam7(data, ihdr),
+    };
+    let compressed = deflate::deflate_bytes_zlib(&filtered);
+    for idat in compressed.chunks(options.idat_chunk_size) {
+        write_chunk(&mut writer, IDAT, idat)?;
+    }
+
+    write_chunk(&mut writer, IEND, &[])?;
+    writer.flush()?;
+    Ok(())
+}
+
+fn write_palette<W: Write>(writer: &mut W, palette: &Palette) -> Result<()> {
+    let (plte, trns) = palette.to_bytes();
+    write_chunk(writer, PLTE, &plte)?;
+    if let Some(trns) = trns {
+        write_chunk(writer, TRNS, &trns)?;
+    }
+    Ok(())
+}
+
+fn write_ancillary<W: Write>(
+    writer: &mut W,
+    chunks: &[&AncillaryChunk],
+    ihdr: &IhdrChunk,
+) -> Result<()> {
+    for chunk in chunks {
+        write_chunk(writer, chunk.chunk_type(), &chunk.to_bytes(ihdr)?)?;
+    }
+    Ok(())
+}
+
+/// Prefixes every scanline with its filter type and filters it
+fn filter_image(data: &[u8], ihdr: &IhdrChunk, width: u32, height: u32) -> Vec<u8> {
+    let scanline_len = ihdr.scanline_len(width);
+    let bpp = ihdr.bytes_per_pixel();
+
+    let mut filtered = vec![0; (scanline_len + 1) * height as usize];
+    let mut prev_scanline = vec![0; scanline_len];
+    for (scanline, filtered_scanline) in data
+        .chunks(scanline_len)
+        .zip(filtered.chunks_mut(scanline_len + 1))
+    {
+        filter_apply::encode_scanline(scanline, &prev_scanline, bpp, filtered_scanline);
+        prev_scanline.copy_from_slice(scanline);
+    }
+    filtered
+}
+
+fn filter_adam7(data: &[u8], ihdr: &IhdrChunk) -> Vec<u8> {
+    let bits_per_pixel = ihdr.bits_per_pixel();
+    let scanline_len = ihdr.scanline_len(ihdr.width);
+    let (width, height) = (ihdr.width as usize, ihdr.height as usize);
+
+    let mut filtered = Vec::new();
+    for ((start_x, start_y), (step_x, step_y)) in ADAM7_PASSES {
+        if width <= start_x || height <= start_y {
+            continue;
+        }
+        let pass_width = (width - start_x).div_ceil(step_x);
+        let pass_height = (height - start_y).div_ceil(step_y);
+        let pass_scanline_len = ihdr.scanline_len(pass_width as u32);
+
+        let mut pass = vec![0; pass_scanline_len * pass_height];
+        for (pass_y, y) in (start_y..height).step_by(step_y).enumerate() {
+            let row = &data[y * scanline_len..(y + 1) * scanline_len];
+            let pass_row = &mut pass[pass_y * pass_scanline_len..(pass_y + 1) * pass_scanline_len];
+            for (pass_x, x) in (start_x..width).step_by(step_x).enumerate() {
+                copy_pixel_bits(row, x, pass_row, pass_x, bits_per_pixel);
+            }
+        }
+
+        filtered.extend(filter_image(
+            &pass,
+            ihdr,
+            pass_width as u32,
+            pass_height as u32,
+        ));
+    }
+    filtered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ancillary_chunks::background::Background;
+    use crate::ancillary_chunks::hist::Histogram;
+    use crate::ancillary_chunks::sbit::SignificantBits;
+    use crate::ancillary_chunks::text::TextChunk;
+    use crate::png_parser::Png;
+
+    fn palette_png(ancillary: &AncillaryChunks, options: &WriteOptions) -> Vec<u8> {
+        let ihdr = IhdrChunk {
+            width: 3,
+            height: 2,
+            bit_depth: 8,
+            color_type: ColorType::Palette(Palette {
+                entries: vec![(255, 0, 0, 255), (0, 255, 0, 128), (16, 32, 64, 255)],
+            }),
+            compression_method: CompressionMethod::Zlib,
+            filter_method: FilterMethod::FiveFilter,
+            interlace_method: InterlaceMethod::None,
+        };
+        let mut png = Vec::new();
+        write_raw(&mut png, &[0, 1, 2, 2, 1, 0], &ihdr, ancillary, options).unwrap();
+        png
+    }
+
+    #[test]
+    fn rgba_round_trip_converts_palette_chunks() {
+        let options = WriteOptions {
+            copy_unsafe_chunks: true,
+            ..Default::default()
+        };
+        let ancillary = AncillaryChunks(vec![
+            AncillaryChunk::sBIT(SignificantBits::Rgb {
+                red: 5,
+                green: 6,
+                blue: 5,
+            }),
+            AncillaryChunk::bKGD(Background { color: (0, 255, 0) }),
+            AncillaryChunk::hIST(Histogram {
+                frequencies: vec![1, 2, 3],
+            }),
+        ]);
+        let source = palette_png(&ancillary, &options);
+        let source = Png::new(&source).unwrap();
+        let pixels = source.get_pixels().unwrap();
+
+        let mut written = Vec::new();
+        write_image(&mut written, &pixels, &source.other_chunks, &options).unwrap();
+        let written = Png::new(&written).unwrap();
+
+        assert_eq!(written.get_pixels().unwrap().samples(), pixels.samples());
+        assert_eq!(
+            written.other_chunks.get_sbit(),
+            Some(SignificantBits::Rgba {
+                red: 5,
+                green: 6,
+                blue: 5,
+                alpha: 8,
+            })
+        );
+        assert_eq!(written.other_chunks.get_background(), Some((0, 255, 0)));
+        assert!(written.other_chunks.get_histogram().is_none());
+    }
+
+    #[test]
+    fn unsafe_chunks_are_dropped_by_default() {
+        let ancillary = AncillaryChunks(vec![
+            AncillaryChunk::sBIT(SignificantBits::Rgb {
+                red: 8,
+                green: 8,
+                blue: 8,
+            }),
+            AncillaryChunk::hIST(Histogram {
+                frequencies: vec![1, 2, 3],
+            }),
+            AncillaryChunk::tEXt(TextChunk {
+                keyword: "Title".into(),
+                text: "kept".into(),
+            }),
+        ]);
+        let written = palette_png(&ancillary, &WriteOptions::default());
+        let written = Png::new(&written).unwrap();
+
+        let chunk_types: Vec<_> = written
+            .other_chunks
+            .0
+            .iter()
+            .map(|c| c.chunk_type())
+            .collect();
+        assert_eq!(chunk_types, [TextChunk::CHUNK_TYPE]);
+    }
+
+    #[test]
+    fn raw_round_trip_keeps_the_data() {
+        let source = palette_png(&AncillaryChunks(Vec::new()), &WriteOptions::default());
+        let source = Png::new(&source).unwrap();
+        let raw = source.get_raw().unwrap();
+        assert_eq!(raw.data, [0, 1, 2, 2, 1, 0]);
+    }
+}
@@ -0,0 +1,59 @@
+use crate::color_type::ColorType;
+use crate::plte::Palette;
+
+/// Unfiltered image data in the layout described by the IHDR,
+/// rows are byte aligned and `stride` bytes long
+#[derive(Debug)]
+pub struct RawImage<'a> {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    pub color_type: &'a ColorType,
+    pub stride: usize,
+    pub data: Vec<u8>,
+}
+
+impl RawImage<'_> {
+    pub fn row(&self, y: u32) -> &[u8] {
+        let start = y as usize * self.stride;
+        &self.data[start..start + self.stride]
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[u8]> {
+        self.data.chunks(self.stride)
+    }
+
+    pub fn palette(&self) -> Option<&Palette> {
+        match self.color_type {
+            ColorType::Palette(palette) => Some(palette),
+            _ => None,
+        }
+    }
+}
+
+/// Copies pixel number `src_x` of `src` into pixel number `dst_x` of `dst`,
+/// both being packed rows of `bits_per_pixel` sized pixels
+pub fn copy_pixel_bits(
+    src: &[u8],
+    src_x: usize,
+    dst: &mut [u8],
+    dst_x: usize,
+    bits_per_pixel: usize,
+) {
+    if bits_per_pixel >= 8 {
+        let bytes = bits_per_pixel / 8;
+        dst[dst_x * bytes..(dst_x + 1) * bytes]
+            .copy_from_slice(&src[src_x * bytes..(src_x + 1) * bytes]);
+        return;
+    }
+
+    let mask = (1u8 << bits_per_pixel) - 1;
+    let src_bit = src_x * bits_per_pixel;
+    let dst_bit = dst_x * bits_per_pixel;
+    let src_shift = 8 - bits_per_pixel - src_bit % 8;
+    let dst_shift = 8 - bits_per_pixel - dst_bit % 8;
+
+    let value = (src[src_bit / 8] >> src_shift) & mask;
+    let dst_byte = &mut dst[dst_bit / 8];
+    *dst_byte = (*dst_byte & !(mask << dst_shift)) | (value << dst_shift);
+}
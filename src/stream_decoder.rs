@@ -0,0 +1,555 @@
+use std::io::Read;
+
+use inflate::InflateStream;
+
+use crate::ancillary_chunks::sbit::SignificantBits;
+use crate::ancillary_chunks::{
+    parse_ancillary_chunks, parse_ancillary_chunks_lenient, AncillaryChunks,
+};
+use crate::chunk::{calculate_crc, parse_chunk_type, RawChunk};
+use crate::error::{PngError, Result};
+use crate::filter_apply;
+use crate::ihdr::{parse_ihdr, IhdrChunk, InterlaceMethod, IHDR};
+use crate::limits::{self, DecodeLimits};
+use crate::plte::{parse_palette, PLTE};
+use crate::png_parser::{pixel16_to_pixel, ADAM7_PASSES, IDAT, IEND, MAGIC_NUMBER, TRNS};
+
+/// Compressed bytes read from the input at once
+const READ_SIZE: usize = 8192;
+
+/// A chunk read from a stream, owning its data
+#[derive(Debug)]
+pub struct OwnedChunk {
+    pub chunk_type: String,
+    pub data: Vec<u8>,
+    /// Byte offset of the start of the chunk in the stream
+    pub offset: usize,
+}
+
+impl OwnedChunk {
+    pub fn as_raw(&self) -> RawChunk<'_> {
+        RawChunk {
+            chunk_type: &self.chunk_type,
+            data: &self.data,
+            offset: self.offset,
+        }
+    }
+}
+
+/// Keeps track of the position in the stream to report the offset of errors
+struct CountingReader<R: Read> {
+    inner: R,
+    position: usize,
+}
+
+impl<R: Read> CountingReader<R> {
+    /// Like `Read::read_exact` but reports a truncated stream at the position of the read
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+        let offset = self.position;
+        std::io::Read::read_exact(self, buf).map_err(|e| match e.kind() {
+            std::io::ErrorKind::UnexpectedEof => PngError::Truncated { offset },
+            _ => PngError::Io(e),
+        })
+    }
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.position += read;
+        Ok(read)
+    }
+}
+
+/// A single decoded scanline
+#[derive(Debug)]
+pub struct Row {
+    /// Index of the Adam7 pass the row belongs to, `None` for non interlaced images
+    pub pass: Option<usize>,
+    pub y: u32,
+    /// Image column of the first pixel in the row
+    pub x_start: u32,
+    /// Distance in image columns between two consecutive pixels of the row
+    pub x_step: u32,
+    /// Unfiltered scanline in the native layout of the png
+    pub raw: Vec<u8>,
+    /// The row's pixels converted to RGBA, 4 samples per pixel
+    pub samples: Vec<u8>,
+}
+
+impl Row {
+    pub fn width(&self) -> usize {
+        self.samples.len() / 4
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Pass {
+    index: Option<usize>,
+    start: (u32, u32),
+    step: (u32, u32),
+    width: u32,
+    height: u32,
+}
+
+/// Decodes a png from a reader, inflating the image data incrementally and yielding one row at a
+/// time so only a few scanlines are kept in memory.
+/// Only the default image is decoded, APNG frames are kept as unknown chunks.
+pub struct StreamDecoder<R: Read> {
+    reader: CountingReader<R>,
+    pub ihdr: IhdrChunk,
+    /// Every chunk other than IHDR, PLTE, tRNS, IDAT and IEND read so far
+    pub chunks: Vec<OwnedChunk>,
+    limits: DecodeLimits,
+    /// Entries of the PLTE chunk, the suggested palette of truecolor images
+    palette_len: Option<usize>,
+    /// Samples are rescaled from their significant bits when set
+    significant_bits: Option<SignificantBits>,
+    inflater: InflateStream,
+    idat_remaining: u32,
+    /// Offset of the IDAT chunk being read
+    idat_offset: usize,
+    idat_crc: crc32fast::Hasher,
+    idat_done: bool,
+    inflated: Vec<u8>,
+    inflated_pos: usize,
+    /// Bytes inflated so far, checked against the limit
+    inflated_total: usize,
+    passes: Vec<Pass>,
+    pass_index: usize,
+    pass_row: u32,
+    prev_scanline: Vec<u8>,
+    finished: bool,
+}
+
+fn read_u32<R: Read>(reader: &mut CountingReader<R>) -> Result<u32> {
+    let mut buf = [0; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_be_bytes(buf))
+}
+
+/// Returns the length, type and offset of the next chunk
+fn read_chunk_header<R: Read>(reader: &mut CountingReader<R>) -> Result<(u32, String, usize)> {
+    let offset = reader.position;
+    let length = read_u32(reader)?;
+    let mut chunk_type = [0; 4];
+    reader.read_exact(&mut chunk_type)?;
+    let chunk_type = parse_chunk_type(&chunk_type, offset)?.to_string();
+    Ok((length, chunk_type, offset))
+}
+
+fn read_chunk_body<R: Read>(
+    reader: &mut CountingReader<R>,
+    length: u32,
+    chunk_type: String,
+    offset: usize,
+) -> Result<OwnedChunk> {
+    let mut data = Vec::new();
+    reader.by_ref().take(length as u64).read_to_end(&mut data)?;
+    if data.len() != length as usize {
+        return Err(PngError::Truncated {
+            offset: reader.position,
+        });
+    }
+    let crc = read_u32(reader)?;
+    if crc != calculate_crc(chunk_type.as_bytes(), &data) {
+        return Err(PngError::Crc { chunk_type, offset });
+    }
+    Ok(OwnedChunk {
+        chunk_type,
+        data,
+        offset,
+    })
+}
+
+/// Critical chunks other than the ones handled by the decoder can't be ignored
+fn check_ancillary(chunk: OwnedChunk) -> Result<OwnedChunk> {
+    if !chunk.as_raw().is_ancillary() {
+        return Err(PngError::UnknownCritical {
+            chunk_type: chunk.chunk_type,
+            offset: chunk.offset,
+        });
+    }
+    Ok(chunk)
+}
+
+impl<R: Read> StreamDecoder<R> {
+    /// Reads the chunks up to the first IDAT chunk
+    pub fn new(reader: R) -> Result<Self> {
+        Self::with_limits(reader, DecodeLimits::default())
+    }
+
+    pub fn with_limits(reader: R, limits: DecodeLimits) -> Result<Self> {
+        let mut reader = CountingReader {
+            inner: reader,
+            position: 0,
+        };
+        let mut magic_number = [0; 8];
+        reader
+            .read_exact(&mut magic_number)
+            .map_err(|_| PngError::Signature)?;
+        if magic_number != MAGIC_NUMBER {
+            return Err(PngError::Signature);
+        }
+
+        let (length, chunk_type, offset) = read_chunk_header(&mut reader)?;
+        if chunk_type != IHDR {
+            return Err(PngError::ChunkOrder {
+                chunk_type,
+                offset,
+                reason: "First chunk isn't IHDR".to_string(),
+            });
+        }
+        let ihdr_chunk = read_chunk_body(&mut reader, length, chunk_type, offset)?;
+
+        let mut plte = None;
+        let mut trns = None;
+        let mut chunks = Vec::new();
+        let (idat_length, idat_offset) = loop {
+            let (length, chunk_type, offset) = read_chunk_header(&mut reader)?;
+            match chunk_type.as_str() {
+                IDAT => break (length, offset),
+                IEND => return Err(PngError::MissingChunk(IDAT)),
+                _ => {}
+            }
+            let chunk = read_chunk_body(&mut reader, length, chunk_type, offset)?;
+            match chunk.chunk_type.as_str() {
+                PLTE => plte = Some(chunk),
+                TRNS => trns = Some(chunk),
+                _ => {
+                    limits.check_chunks(chunks.len() + 1)?;
+                    chunks.push(check_ancillary(chunk)?);
+                }
+            }
+        };
+
+        let palette = plte
+            .map(|plte| {
+                parse_palette(
+                    &plte.as_raw(),
+                    trns.as_ref().map(OwnedChunk::as_raw).as_ref(),
+                )
+            })
+            .transpose()?;
+        let palette_len = palette.as_ref().map(|palette| palette.entries.len());
+        let trns = if palette.is_some() { None } else { trns };
+        let (_, ihdr) = parse_ihdr(
+            &ihdr_chunk.data,
+            ihdr_chunk.offset,
+            palette,
+            trns.as_ref().map(|c| &c.data[..]),
+            &limits,
+        )?;
+
+        let mut idat_crc = crc32fast::Hasher::new();
+        idat_crc.update(IDAT.as_bytes());
+
+        let passes = match ihdr.interlace_method {
+            InterlaceMethod::None => vec![Pass {
+                index: None,
+                start: (0, 0),
+                step: (1, 1),
+                width: ihdr.width,
+                height: ihdr.height,
+            }],
+            InterlaceMethod::Adam7 => ADAM7_PASSES
+                .iter()
+                .enumerate()
+                .filter(|(_, ((start_x, start_y), _))| {
+                    (*start_x as u32) < ihdr.width && (*start_y as u32) < ihdr.height
+                })
+                .map(|(i, &((start_x, start_y), (step_x, step_y)))| Pass {
+                    index: Some(i),
+                    start: (start_x as u32, start_y as u32),
+                    step: (step_x as u32, step_y as u32),
+                    width: (ihdr.width - start_x as u32).div_ceil(step_x as u32),
+                    height: (ihdr.height - start_y as u32).div_ceil(step_y as u32),
+                })
+                .collect(),
+        };
+        let prev_scanline = vec![0; ihdr.scanline_len(passes[0].width)];
+        let significant_bits = chunks
+            .iter()
+            .find(|c| c.chunk_type == SignificantBits::CHUNK_TYPE)
+            .map(|c| {
+                SignificantBits::parse(&c.data, &ihdr.color_type, ihdr.sample_depth())
+                    .map_err(|e| e.at_offset(c.offset))
+            })
+            .transpose()?;
+
+        Ok(Self {
+            reader,
+            ihdr,
+            chunks,
+            limits,
+            palette_len,
+            significant_bits,
+            inflater: InflateStream::from_zlib(),
+            idat_remaining: idat_length,
+            idat_offset,
+            idat_crc,
+            idat_done: false,
+            inflated: Vec::new(),
+            inflated_pos: 0,
+            inflated_total: 0,
+            passes,
+            pass_index: 0,
+            pass_row: 0,
+            prev_scanline,
+            finished: false,
+        })
+    }
+
+    /// Parses the ancillary chunks read so far, trailing chunks are available once every row
+    /// was read
+    pub fn ancillary_chunks(&self) -> Result<AncillaryChunks<'_>> {
+        let chunks = self.chunks.iter().map(OwnedChunk::as_raw).collect();
+        Ok(AncillaryChunks(parse_ancillary_chunks(
+            chunks,
+            &self.ihdr,
+            self.palette_len,
+            &self.limits,
+        )?))
+    }
+
+    /// Parses the ancillary chunks like [`Self::ancillary_chunks`], chunks that can't be parsed
+    /// are left out and returned as warnings
+    pub fn ancillary_chunks_lenient(&self) -> (AncillaryChunks<'_>, Vec<PngError>) {
+        let chunks = self.chunks.iter().map(OwnedChunk::as_raw).collect();
+        let (chunks, warnings) =
+            parse_ancillary_chunks_lenient(chunks, &self.ihdr, self.palette_len, &self.limits);
+        (AncillaryChunks(chunks), warnings)
+    }
+
+    /// Reads compressed data from the current IDAT chunk, moving on to the next one when it
+    /// is exhausted. Returns false once the IDAT chunks are over.
+    fn inflate_more(&mut self) -> Result<bool> {
+        while self.idat_remaining == 0 {
+            if self.idat_done {
+                return Ok(false);
+            }
+            let crc = read_u32(&mut self.reader)?;
+            let hasher = std::mem::replace(&mut self.idat_crc, crc32fast::Hasher::new());
+            if crc != hasher.finalize() {
+                return Err(PngError::Crc {
+                    chunk_type: IDAT.to_string(),
+                    offset: self.idat_offset,
+                });
+            }
+
+            let (length, chunk_type, offset) = read_chunk_header(&mut self.reader)?;
+            if chunk_type != IDAT {
+                self.idat_done = true;
+                let chunk = read_chunk_body(&mut self.reader, length, chunk_type, offset)?;
+                self.push_trailing_chunk(chunk)?;
+                return Ok(false);
+            }
+            self.idat_crc.update(IDAT.as_bytes());
+            self.idat_remaining = length;
+            self.idat_offset = offset;
+        }
+
+        let mut compressed = vec![0; (self.idat_remaining as usize).min(READ_SIZE)];
+        self.reader.read_exact(&mut compressed)?;
+        self.idat_remaining -= compressed.len() as u32;
+        self.idat_crc.update(&compressed);
+
+        if self.inflated_pos > 0 {
+            self.inflated.drain(..self.inflated_pos);
+            self.inflated_pos = 0;
+        }
+
+        let mut consumed = 0;
+        loop {
+            let (read, output) = self
+                .inflater
+                .update(&compressed[consumed..])
+                .map_err(|e| PngError::decompression(IDAT, e).at_offset(self.idat_offset))?;
+            consumed += read;
+            if output.is_empty() && (read == 0 || consumed == compressed.len()) {
+                break;
+            }
+            self.inflated_total += output.len();
+            limits::check(
+                "inflated bytes",
+                self.inflated_total as u64,
+                self.limits.max_inflated_bytes as u64,
+            )?;
+            self.inflated.extend_from_slice(output);
+        }
+        Ok(true)
+    }
+
+    fn push_trailing_chunk(&mut self, chunk: OwnedChunk) -> Result<()> {
+        if chunk.chunk_type == IEND {
+            if !chunk.data.is_empty() {
+                return Err(PngError::ChunkOrder {
+                    chunk_type: chunk.chunk_type,
+                    offset: chunk.offset,
+                    reason: "IEND isn't empty".to_string(),
+                });
+            }
+            self.finished = true;
+        } else {
+            self.limits.check_chunks(self.chunks.len() + 1)?;
+            self.chunks.push(check_ancillary(chunk)?);
+        }
+        Ok(())
+    }
+
+    /// Reads the chunks after the image data until IEND
+    fn read_trailing_chunks(&mut self) -> Result<()> {
+        while self.inflate_more()? {}
+        while !self.finished {
+            let (length, chunk_type, offset) = read_chunk_header(&mut self.reader)?;
+            if chunk_type == IDAT {
+                return Err(PngError::ChunkOrder {
+                    chunk_type,
+                    offset,
+                    reason: "IDAT chunks aren't consecutive".to_string(),
+                });
+            }
+            let chunk = read_chunk_body(&mut self.reader, length, chunk_type, offset)?;
+            self.push_trailing_chunk(chunk)?;
+        }
+        Ok(())
+    }
+
+    /// Decodes the next row, rows of interlaced images are yielded pass after pass.
+    /// Returns `None` after the last row once the rest of the stream was read up to IEND.
+    pub fn next_row(&mut self) -> Result<Option<Row>> {
+        let Some(&pass) = self.passes.get(self.pass_index) else {
+            if !self.finished {
+                self.read_trailing_chunks()?;
+            }
+            return Ok(None);
+        };
+
+        let scanline_len = 1 + self.ihdr.scanline_len(pass.width);
+        while self.inflated.len() - self.inflated_pos < scanline_len {
+            if !self.inflate_more()? {
+                return Err(
+                    PngError::image_data("Image data ended before the last scanline")
+                        .at_offset(self.idat_offset),
+                );
+            }
+        }
+        let scanline = &self.inflated[self.inflated_pos..self.inflated_pos + scanline_len];
+        self.inflated_pos += scanline_len;
+
+        let at_idat = |e: PngError| e.at_offset(self.idat_offset);
+        let mut raw = vec![0; scanline_len - 1];
+        filter_apply::decode_scanline(
+            scanline,
+            &self.prev_scanline,
+            self.ihdr.bytes_per_pixel(),
+            &mut raw,
+        )
+        .map_err(at_idat)?;
+
+        let mut samples = Vec::with_capacity(pass.width as usize * 4);
+        let mut scanline_reader = bitreader::BitReader::new(&raw);
+        for _ in 0..pass.width {
+            let color_type = &self.ihdr.color_type;
+            let (r, g, b, a) = match &self.significant_bits {
+                Some(sbit) => pixel16_to_pixel(
+                    sbit.rescale(
+                        color_type
+                            .read_pixel16(self.ihdr.bit_depth, &mut scanline_reader)
+                            .map_err(at_idat)?,
+                        self.ihdr.sample_depth(),
+                    ),
+                ),
+                None => color_type
+                    .read_pixel(self.ihdr.bit_depth, &mut scanline_reader)
+                    .map_err(at_idat)?,
+            };
+            samples.extend_from_slice(&[r, g, b, a]);
+        }
+
+        let row = Row {
+            pass: pass.index,
+            y: pass.start.1 + self.pass_row * pass.step.1,
+            x_start: pass.start.0,
+            x_step: pass.step.0,
+            raw,
+            samples,
+        };
+
+        self.pass_row += 1;
+        if self.pass_row == pass.height {
+            self.pass_row = 0;
+            self.pass_index += 1;
+            if let Some(next) = self.passes.get(self.pass_index) {
+                self.prev_scanline = vec![0; self.ihdr.scanline_len(next.width)];
+            }
+        } else {
+            self.prev_scanline.clone_from(&row.raw);
+        }
+
+        Ok(Some(row))
+    }
+}
+
+impl<R: Read> Iterator for StreamDecoder<R> {
+    type Item = Result<Row>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_row().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png_parser::{Image, Png};
+    use crate::test_support::rgba_png;
+
+    /// Reads every row, returning the first error
+    fn read_rows<R: Read>(decoder: &mut StreamDecoder<R>) -> Result<Vec<Row>> {
+        let mut rows = Vec::new();
+        while let Some(row) = decoder.next_row()? {
+            rows.push(row);
+        }
+        Ok(rows)
+    }
+
+    #[test]
+    fn rows_match_the_decoded_image() {
+        for interlace_method in [InterlaceMethod::None, InterlaceMethod::Adam7] {
+            let png = rgba_png(13, 11, interlace_method);
+            assert!(png.windows(4).filter(|w| w == b"IDAT").count() > 1);
+            let expected = Png::new(&png).unwrap().get_pixels().unwrap();
+
+            let mut decoder = StreamDecoder::new(&png[..]).unwrap();
+            let rows = read_rows(&mut decoder).unwrap();
+            let mut image = Image::new(13, 11);
+            for row in &rows {
+                for (i, s) in row.samples.chunks_exact(4).enumerate() {
+                    let x = row.x_start + i as u32 * row.x_step;
+                    image.set_pixel(x, row.y, (s[0], s[1], s[2], s[3]));
+                }
+            }
+            assert_eq!(image, expected);
+            let pixels: usize = rows.iter().map(Row::width).sum();
+            assert_eq!(pixels, 13 * 11);
+        }
+    }
+
+    #[test]
+    fn inflated_data_is_bounded() {
+        let png = rgba_png(64, 64, InterlaceMethod::None);
+        let limits = DecodeLimits {
+            max_inflated_bytes: 1000,
+            ..Default::default()
+        };
+        let mut decoder = StreamDecoder::with_limits(&png[..], limits).unwrap();
+        assert!(matches!(
+            read_rows(&mut decoder),
+            Err(PngError::LimitExceeded {
+                limit: "inflated bytes",
+                ..
+            })
+        ));
+    }
+}
@@ -0,0 +1,34 @@
+//! Fixtures shared by the unit tests
+
+use crate::ancillary_chunks::AncillaryChunks;
+use crate::color_type::ColorType;
+use crate::ihdr::{CompressionMethod, FilterMethod, IhdrChunk, InterlaceMethod};
+use crate::png_writer::{write_raw, WriteOptions};
+
+/// An 8 bit RGBA png of varied samples, split in small IDAT chunks so rows span several of them
+pub fn rgba_png(width: u32, height: u32, interlace_method: InterlaceMethod) -> Vec<u8> {
+    let ihdr = IhdrChunk {
+        width,
+        height,
+        bit_depth: 8,
+        color_type: ColorType::Rgba,
+        compression_method: CompressionMethod::Zlib,
+        filter_method: FilterMethod::FiveFilter,
+        interlace_method,
+    };
+    let data: Vec<u8> = (0..width * height * 4).map(|i| (i * 7) as u8).collect();
+    let options = WriteOptions {
+        idat_chunk_size: 16,
+        ..Default::default()
+    };
+    let mut png = Vec::new();
+    write_raw(
+        &mut png,
+        &data,
+        &ihdr,
+        &AncillaryChunks(Vec::new()),
+        &options,
+    )
+    .unwrap();
+    png
+}